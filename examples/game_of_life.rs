@@ -1,4 +1,5 @@
 use cgmath::Vector2;
+use floppa2::renderer::backend::{RenderBackend, SpriteFactory};
 use floppa2::renderer::sprite::Sprite;
 use floppa2::renderer::{Color, Renderer};
 use floppa2::renderer_ext::RendererExt;
@@ -23,7 +24,7 @@ struct World {
 impl World {
     fn empty(size: Vector2<usize>) -> World {
         World {
-            size: size,
+            size,
             data: vec![false; size.x * size.y],
         }
     }
@@ -94,7 +95,7 @@ struct Game {
 }
 
 impl Game {
-    fn new(renderer: &Renderer) -> Game {
+    fn new(renderer: &impl SpriteFactory) -> Game {
         Game {
             world: World::filled_at((CELL_COUNT as usize, CELL_COUNT as usize).into(), 0.5),
             highlighted: None,
@@ -107,7 +108,7 @@ impl Game {
         self.world = self.world.step();
     }
 
-    fn render(&self, renderer: &Renderer) {
+    fn render(&self, renderer: &impl RenderBackend) {
         renderer.render(|ctx| {
             ctx.set_clear_color(Color::BLUE);
             for x in 0..CELL_COUNT {
@@ -126,7 +127,7 @@ impl Game {
                         (false, true) => &self.tile_frame,
                         _ => &self.tile_sprite,
                     };
-                    ctx.draw(&cell_sprite)
+                    ctx.draw(cell_sprite)
                         .at(cell_position)
                         .with_color(cell_color);
                 }
@@ -173,18 +174,15 @@ fn main() {
             WindowEvent::KeyboardInput {
                 input:
                     KeyboardInput {
-                        virtual_keycode: Some(key),
+                        virtual_keycode: Some(VirtualKeyCode::N),
                         state: ElementState::Released,
                         ..
                     },
                 ..
-            } => match key {
-                VirtualKeyCode::N => {
-                    game.step();
-                    window.request_redraw()
-                }
-                _ => (),
-            },
+            } => {
+                game.step();
+                window.request_redraw()
+            }
             WindowEvent::CursorMoved { position, .. } => {
                 game.on_mouse_move(
                     (position.x as f32, WINDOW_SIZE as f32 - position.y as f32).into(),
@@ -0,0 +1,160 @@
+use std::cell::{Ref, RefCell};
+
+use cgmath::Vector2;
+
+use super::{
+    sprite::Sprite, texture_ref::TextureRefManager, RenderCommands, Renderer, TextureData,
+};
+
+/// Something that can create sprites
+pub trait SpriteFactory {
+    /// Creates a sprite, loading data provided in a param to it
+    fn create_sprite(&self, data: impl TextureData) -> Sprite;
+}
+
+/// Something that can render frames described by [`RenderCommands`]
+///
+/// Allows game code to be tested with a [`RecordingRenderer`] instead of a [`Renderer`].
+pub trait RenderBackend: SpriteFactory {
+    /// Renders things described by a callback
+    fn render(&self, callback: impl FnOnce(&mut RenderCommands));
+
+    /// Notifies backend about target's resize
+    fn resize(&self, size: Vector2<u32>);
+}
+
+impl SpriteFactory for Renderer {
+    fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        Renderer::create_sprite(self, data)
+    }
+}
+
+impl RenderBackend for Renderer {
+    fn render(&self, callback: impl FnOnce(&mut RenderCommands)) {
+        Renderer::render(self, callback);
+    }
+
+    fn resize(&self, size: Vector2<u32>) {
+        Renderer::resize(self, size);
+    }
+}
+
+/// Sprite created by a [`RecordingRenderer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedSprite {
+    /// ID of the sprite, as returned by [`Sprite::id`]
+    pub id: usize,
+    pub size: Vector2<u32>,
+    /// Sprite's RGBA bytes
+    pub data: Vec<u8>,
+}
+
+/// Render backend which doesn't render anything, but keeps created sprites and rendered frames
+///
+/// Sprites get the same IDs as ones created by a [`Renderer`] in the same order.
+#[derive(Default)]
+pub struct RecordingRenderer {
+    texture_ref_manager: TextureRefManager,
+    sprites: RefCell<Vec<CreatedSprite>>,
+    frames: RefCell<Vec<RenderCommands>>,
+    size: RefCell<Option<Vector2<u32>>>,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> RecordingRenderer {
+        RecordingRenderer::default()
+    }
+
+    /// Returns sprites created so far, in creation order
+    pub fn sprites(&self) -> Ref<'_, [CreatedSprite]> {
+        Ref::map(self.sprites.borrow(), Vec::as_slice)
+    }
+
+    /// Returns frames rendered so far, in rendering order
+    pub fn frames(&self) -> Ref<'_, [RenderCommands]> {
+        Ref::map(self.frames.borrow(), Vec::as_slice)
+    }
+
+    /// Returns the last rendered frame, if any
+    pub fn last_frame(&self) -> Option<Ref<'_, RenderCommands>> {
+        Ref::filter_map(self.frames.borrow(), |frames| frames.last()).ok()
+    }
+
+    /// Returns size passed to the last resize, if any
+    pub fn size(&self) -> Option<Vector2<u32>> {
+        *self.size.borrow()
+    }
+}
+
+impl SpriteFactory for RecordingRenderer {
+    fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        let sprite = Sprite {
+            texture: self.texture_ref_manager.next(),
+            size: data.size(),
+        };
+        self.sprites.borrow_mut().push(CreatedSprite {
+            id: sprite.id(),
+            size: sprite.size,
+            data: data.data(),
+        });
+        sprite
+    }
+}
+
+impl RenderBackend for RecordingRenderer {
+    fn render(&self, callback: impl FnOnce(&mut RenderCommands)) {
+        let mut commands = RenderCommands::default();
+        callback(&mut commands);
+        self.frames.borrow_mut().push(commands);
+    }
+
+    fn resize(&self, size: Vector2<u32>) {
+        *self.size.borrow_mut() = Some(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector2;
+
+    use crate::renderer::{sprite::Sprite, testing::scenes::Pixel, Color};
+
+    use super::{RecordingRenderer, RenderBackend, SpriteFactory};
+
+    fn create_sprites(factory: &impl SpriteFactory) -> (Sprite, Sprite) {
+        (
+            factory.create_sprite(Pixel([255; 4])),
+            factory.create_sprite(Pixel([255; 4])),
+        )
+    }
+
+    fn draw_cursor(backend: &impl RenderBackend, cursor: &Sprite, at: (u32, u32)) {
+        backend.render(|commands| {
+            commands.set_clear_color(Color::BLUE);
+            commands.draw(cursor).at(at).with_color(Color::RED);
+        });
+    }
+
+    #[test]
+    fn test_recording_renderer_keeps_sprites_and_frames() {
+        let renderer = RecordingRenderer::new();
+        let (_, cursor) = create_sprites(&renderer);
+        draw_cursor(&renderer, &cursor, (3, 4));
+        renderer.resize((8, 8).into());
+
+        assert_eq!(
+            vec![0, 1],
+            renderer.sprites().iter().map(|s| s.id).collect::<Vec<_>>()
+        );
+        assert_eq!(Some((8, 8).into()), renderer.size());
+        assert_eq!(1, renderer.frames().len());
+        let frame = renderer.last_frame().unwrap();
+        assert_eq!(Some(Color::BLUE), frame.clear_color());
+        let [blit] = frame.blits() else {
+            panic!("Expected a single blit");
+        };
+        assert_eq!(cursor.id(), blit.sprite_id());
+        assert_eq!(Vector2::new(3, 4), blit.position());
+        assert_eq!(Color::RED, blit.color());
+    }
+}
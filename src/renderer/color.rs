@@ -0,0 +1,292 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// Converts a single sRGB encoded color component to linear space
+pub fn srgb_to_linear(component: f32) -> f32 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a single linear color component to sRGB encoding
+pub fn linear_to_srgb(component: f32) -> f32 {
+    if component <= 0.0031308 {
+        component * 12.92
+    } else {
+        1.055 * component.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// An RGBA color, with components in linear space
+///
+/// Tints are multiplied with sprites' colors and blended in linear space, which is then encoded
+/// to sRGB on a window. Colors picked in design tools are sRGB encoded, so they should be created
+/// with sRGB constructors, like [`Color::rgba8`], [`Color::from_hex`] or [`Color::from_hsv`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// Error returned when a hex color code cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseColorError {
+    /// Code has other number of digits than 3, 4, 6 or 8
+    InvalidLength(usize),
+    /// Code contains a character which is not a hex digit
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseColorError::InvalidLength(length) => {
+                write!(f, "expected 3, 4, 6 or 8 hex digits, got {}", length)
+            }
+            ParseColorError::InvalidDigit(digit) => write!(f, "invalid hex digit {:?}", digit),
+        }
+    }
+}
+
+impl Error for ParseColorError {}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::new(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+    pub const RED: Color = Color::new(1.0, 0.0, 0.0, 1.0);
+    pub const GREEN: Color = Color::new(0.0, 1.0, 0.0, 1.0);
+    pub const BLUE: Color = Color::new(0.0, 0.0, 1.0, 1.0);
+    pub const YELLOW: Color = Color::new(1.0, 1.0, 0.0, 1.0);
+    pub const CYAN: Color = Color::new(0.0, 1.0, 1.0, 1.0);
+    pub const MAGENTA: Color = Color::new(1.0, 0.0, 1.0, 1.0);
+
+    /// Creates color from linear components
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Creates color from sRGB encoded components in range `0.0..=1.0`, alpha is kept linear
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// Creates color from 8-bit sRGB encoded components
+    pub fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        let unit = |component| component as f32 / 255.0;
+        Color::from_srgb(unit(r), unit(g), unit(b), unit(a))
+    }
+
+    /// Parses sRGB hex code, like `#ff8800`, `#f80`, `#ff880080` or `#f808`, `#` being optional
+    pub fn from_hex(hex: &str) -> Result<Color, ParseColorError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let values = digits
+            .chars()
+            .map(|digit| {
+                digit
+                    .to_digit(16)
+                    .map(|value| value as u8)
+                    .ok_or(ParseColorError::InvalidDigit(digit))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let bytes: Vec<_> = match values.len() {
+            3 | 4 => values.iter().map(|value| value * 0x11).collect(),
+            6 | 8 => values
+                .chunks(2)
+                .map(|pair| pair[0] << 4 | pair[1])
+                .collect(),
+            length => return Err(ParseColorError::InvalidLength(length)),
+        };
+        let alpha = bytes.get(3).copied().unwrap_or(u8::MAX);
+        Ok(Color::rgba8(bytes[0], bytes[1], bytes[2], alpha))
+    }
+
+    /// Creates opaque color from sRGB hue in degrees, saturation and value in range `0.0..=1.0`
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let chroma = value * saturation;
+        Color::from_chroma(hue, chroma, value - chroma)
+    }
+
+    /// Creates opaque color from sRGB hue in degrees, saturation and lightness in range `0.0..=1.0`
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        Color::from_chroma(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// Returns copy of the color with a given alpha
+    pub fn with_alpha(self, a: f32) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Linearly interpolates between colors, `t` being `0.0` for `self` and `1.0` for `other`
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+        Color::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// Returns color with its components multiplied by its alpha
+    pub fn premultiply(self) -> Color {
+        Color::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// Returns sRGB encoded components, alpha is kept linear
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    /// Returns 8-bit sRGB encoded components
+    pub fn to_rgba8(self) -> [u8; 4] {
+        self.to_srgb()
+            .map(|component| (component.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Builds sRGB color from hue, chroma and a value added to every component
+    fn from_chroma(hue: f32, chroma: f32, m: f32) -> Color {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Color::from_srgb(r + m, g + m, b + m, 1.0)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::from_hex(s)
+    }
+}
+
+impl From<wgpu::Color> for Color {
+    fn from(color: wgpu::Color) -> Self {
+        Color::new(
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        )
+    }
+}
+
+impl From<Color> for wgpu::Color {
+    fn from(color: Color) -> Self {
+        wgpu::Color {
+            r: color.r as f64,
+            g: color.g as f64,
+            b: color.b as f64,
+            a: color.a as f64,
+        }
+    }
+}
+
+impl From<image::Rgba<u8>> for Color {
+    fn from(pixel: image::Rgba<u8>) -> Self {
+        let [r, g, b, a] = pixel.0;
+        Color::rgba8(r, g, b, a)
+    }
+}
+
+impl From<Color> for image::Rgba<u8> {
+    fn from(color: Color) -> Self {
+        image::Rgba(color.to_rgba8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, ParseColorError};
+
+    #[test]
+    fn test_rgba8_round_trip() {
+        for value in 0..=u8::MAX {
+            let color = Color::rgba8(value, value, value, value);
+            assert_eq!([value; 4], color.to_rgba8());
+        }
+        let mid_gray = Color::rgba8(128, 128, 128, 255);
+        assert!((mid_gray.r - 0.2158).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let orange = Color::rgba8(255, 136, 0, 255);
+        assert_eq!(Ok(orange), Color::from_hex("#ff8800"));
+        assert_eq!(Ok(orange), Color::from_hex("FF8800"));
+        assert_eq!(Ok(orange), Color::from_hex("#f80"));
+        assert_eq!(Ok(orange), "#ff8800ff".parse());
+        assert_eq!(
+            [255, 136, 0, 136],
+            Color::from_hex("#f808").unwrap().to_rgba8()
+        );
+        assert_eq!(
+            Err(ParseColorError::InvalidLength(5)),
+            Color::from_hex("#ff880")
+        );
+        assert_eq!(
+            Err(ParseColorError::InvalidDigit('g')),
+            Color::from_hex("#ff88g0")
+        );
+    }
+
+    #[test]
+    fn test_from_hsv_and_hsl() {
+        assert_eq!(
+            [255, 136, 0, 255],
+            Color::from_hsv(32.0, 1.0, 1.0).to_rgba8()
+        );
+        assert_eq!(
+            [0, 0, 255, 255],
+            Color::from_hsv(240.0, 1.0, 1.0).to_rgba8()
+        );
+        assert_eq!(
+            [255, 0, 0, 255],
+            Color::from_hsv(-360.0, 1.0, 1.0).to_rgba8()
+        );
+        assert_eq!(
+            [255, 136, 0, 255],
+            Color::from_hsl(32.0, 1.0, 0.5).to_rgba8()
+        );
+        assert_eq!(
+            [255, 255, 255, 255],
+            Color::from_hsl(0.0, 0.0, 1.0).to_rgba8()
+        );
+    }
+
+    #[test]
+    fn test_lerp_and_premultiply() {
+        let color = Color::BLACK.lerp(Color::WHITE.with_alpha(0.0), 0.25);
+        assert_eq!(Color::new(0.25, 0.25, 0.25, 0.75), color);
+        assert_eq!(
+            Color::new(0.1875, 0.1875, 0.1875, 0.75),
+            color.premultiply()
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        let color = Color::new(0.5, 0.25, 1.0, 0.0);
+        assert_eq!(color, Color::from(wgpu::Color::from(color)));
+        let pixel = image::Rgba([255, 136, 0, 128]);
+        assert_eq!(pixel, image::Rgba::from(Color::from(pixel)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+    slice,
+};
+
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    frame::FrameHandle, sprite::Sprite, texture_ref::TextureRef, RenderCommands, Renderer,
+    TextureData,
+};
+
+/// Commands sent to a renderer, which can be saved to disk and replayed later
+///
+/// Log contains pixels of sprites created while recording, so it can be replayed without a game
+/// which recorded it. Sprites created before recording started are referenced by their
+/// [`Sprite::id`], so a replaying renderer has to create the same sprites in the same order first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandLog {
+    entries: Vec<LogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum LogEntry {
+    CreateSprite {
+        id: TextureRef,
+        size: Vector2<u32>,
+        data: Vec<u8>,
+    },
+    UpdateSprite {
+        id: TextureRef,
+        size: Vector2<u32>,
+        data: Vec<u8>,
+    },
+    UnloadSprite(TextureRef),
+    Resize(Vector2<u32>),
+    Render(RenderCommands),
+}
+
+impl CommandLog {
+    /// Returns number of recorded frames
+    pub fn frame_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, LogEntry::Render(_)))
+            .count()
+    }
+
+    /// Saves log to a JSON file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Loads log saved with [`CommandLog::save`]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<CommandLog> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Returns iterator which sends recorded frames to a renderer, one frame per item
+    ///
+    /// Sprites and resizes recorded before a frame are sent along with it.
+    pub fn replay<'a>(&'a self, renderer: &'a Renderer) -> Replay<'a> {
+        Replay {
+            entries: self.entries.iter(),
+            renderer,
+            sprites: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn log_sprite(&mut self, sprite: &Sprite, data: Vec<u8>) {
+        self.entries.push(LogEntry::CreateSprite {
+            id: sprite.texture,
+            size: sprite.size,
+            data,
+        });
+    }
+
+    pub(crate) fn log_sprite_update(&mut self, sprite: &Sprite, data: Vec<u8>) {
+        self.entries.push(LogEntry::UpdateSprite {
+            id: sprite.texture,
+            size: sprite.size,
+            data,
+        });
+    }
+
+    pub(crate) fn log_sprite_unload(&mut self, sprite: &Sprite) {
+        self.entries.push(LogEntry::UnloadSprite(sprite.texture));
+    }
+
+    pub(crate) fn log_resize(&mut self, size: Vector2<u32>) {
+        self.entries.push(LogEntry::Resize(size));
+    }
+
+    pub(crate) fn log_render(&mut self, commands: &RenderCommands) {
+        self.entries.push(LogEntry::Render(commands.clone()));
+    }
+}
+
+/// Iterator over frames of a [`CommandLog`] being replayed
+pub struct Replay<'a> {
+    entries: slice::Iter<'a, LogEntry>,
+    renderer: &'a Renderer,
+    /// Sprites created during replay, by their recorded IDs
+    sprites: HashMap<TextureRef, Sprite>,
+}
+
+impl Iterator for Replay<'_> {
+    type Item = FrameHandle;
+
+    fn next(&mut self) -> Option<FrameHandle> {
+        for entry in self.entries.by_ref() {
+            match entry {
+                LogEntry::CreateSprite { id, size, data } => {
+                    let sprite = self.renderer.create_sprite(RecordedSprite { size, data });
+                    self.sprites.insert(*id, sprite);
+                }
+                LogEntry::UpdateSprite { id, size, data } => {
+                    let sprite = self.sprites.entry(*id).or_insert(Sprite {
+                        texture: *id,
+                        size: *size,
+                    });
+                    self.renderer
+                        .update_sprite(sprite, RecordedSprite { size, data });
+                }
+                LogEntry::UnloadSprite(id) => {
+                    // Mapping is kept, so later blits of the sprite are skipped as they were
+                    let texture = self.sprites.get(id).map_or(*id, |sprite| sprite.texture);
+                    self.renderer.unload_sprite(Sprite {
+                        texture,
+                        size: Vector2::new(0, 0),
+                    });
+                }
+                LogEntry::Resize(size) => self.renderer.resize(*size),
+                LogEntry::Render(commands) => {
+                    let mut commands = commands.clone();
+                    for blit in commands.blits.iter_mut() {
+                        if let Some(sprite) = self.sprites.get(&blit.texture_id) {
+                            blit.texture_id = sprite.texture;
+                        }
+                    }
+                    return Some(self.renderer.render(|target| *target = commands));
+                }
+            }
+        }
+        None
+    }
+}
+
+struct RecordedSprite<'a> {
+    size: &'a Vector2<u32>,
+    data: &'a [u8],
+}
+
+impl TextureData for RecordedSprite<'_> {
+    fn data(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    fn size(&self) -> Vector2<u32> {
+        *self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{
+        config::RendererConfig,
+        testing::{scenes::Pixel, skip_without_adapter},
+        Color, Renderer,
+    };
+
+    use super::CommandLog;
+
+    #[test]
+    fn test_replay_renders_recorded_frames() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((4, 4), RendererConfig::default()))
+        else {
+            return;
+        };
+        // Created before recording, so replay has to create it too
+        let red = renderer.create_sprite(Pixel([255, 0, 0, 255]));
+        renderer.record_commands();
+        let green = renderer.create_sprite(Pixel([0, 255, 0, 255]));
+        let expected = renderer.capture_next_frame();
+        renderer.render(|commands| {
+            commands.set_clear_color(Color::BLUE);
+            commands.draw(&red).at((1, 1));
+            commands.draw(&green).at((2, 2));
+        });
+        let log = renderer.stop_recording_commands().unwrap();
+        let expected = expected.recv().unwrap();
+        // Some GL drivers don't support multiple devices at once
+        renderer.shutdown().unwrap();
+
+        let json = serde_json::to_string(&log).unwrap();
+        let log: CommandLog = serde_json::from_str(&json).unwrap();
+        assert_eq!(1, log.frame_count());
+
+        let renderer = Renderer::headless((4, 4), RendererConfig::default()).unwrap();
+        renderer.create_sprite(Pixel([255, 0, 0, 255]));
+        // Shifts IDs of sprites created during replay, so they have to be mapped
+        renderer.create_sprite(Pixel([0, 0, 0, 0]));
+        let frame = renderer.capture_next_frame();
+        assert_eq!(1, log.replay(&renderer).count());
+        assert_eq!(expected, frame.recv().unwrap());
+    }
+}
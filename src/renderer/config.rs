@@ -0,0 +1,265 @@
+/// Graphics APIs renderer is allowed to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backends {
+    /// Any backend available on a platform
+    #[default]
+    All,
+    /// Backends with first-class support: Vulkan, Metal, DX12 and WebGPU
+    Primary,
+    /// Vulkan, on Windows, Linux and Android
+    Vulkan,
+    /// OpenGL / OpenGL ES
+    Gl,
+    /// Metal, on macOS and iOS
+    Metal,
+    /// DirectX 12, on Windows
+    Dx12,
+}
+
+/// Preference between integrated and discrete GPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerPreference {
+    /// Lets a platform decide
+    #[default]
+    Default,
+    /// Prefers integrated GPUs
+    LowPower,
+    /// Prefers discrete GPUs
+    HighPerformance,
+}
+
+/// How rendered frames are synchronized with a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /// Waits for a vertical blank, never tearing. Supported everywhere
+    #[default]
+    Vsync,
+    /// Presents frames right away, possibly tearing
+    Immediate,
+    /// Replaces a queued frame with the newest one, without tearing or blocking
+    Mailbox,
+}
+
+/// Describes how renderer should set up a GPU and a window's surface
+///
+/// Options which are not supported by a platform fall back to a closest supported choice:
+/// - if no adapter matches [`RendererConfig::backends`] or
+///   [`RendererConfig::force_fallback_adapter`], any available adapter is used,
+/// - unsupported present modes fall back to [`PresentMode::Vsync`],
+/// - the first surface format and alpha mode offered by a surface are used when no better
+///   match for [`RendererConfig::prefer_srgb`] and [`RendererConfig::transparent`] exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RendererConfig {
+    pub(crate) backends: Backends,
+    pub(crate) power_preference: PowerPreference,
+    pub(crate) force_fallback_adapter: bool,
+    pub(crate) present_mode: PresentMode,
+    pub(crate) prefer_srgb: bool,
+    pub(crate) transparent: bool,
+    pub(crate) max_frames_in_flight: usize,
+    pub(crate) gpu_timing: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        RendererConfig {
+            backends: Backends::default(),
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+            present_mode: PresentMode::default(),
+            prefer_srgb: true,
+            transparent: false,
+            max_frames_in_flight: 2,
+            gpu_timing: false,
+        }
+    }
+}
+
+impl RendererConfig {
+    /// Sets graphics APIs that can be used
+    pub fn backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Sets preference between integrated and discrete GPUs
+    pub fn power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Forces usage of a fallback (usually software) adapter
+    pub fn force_fallback_adapter(mut self, force: bool) -> Self {
+        self.force_fallback_adapter = force;
+        self
+    }
+
+    /// Sets how frames are synchronized with a display
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Prefers sRGB surface formats over the one suggested by a platform, enabled by default
+    ///
+    /// Without an sRGB surface colors are encoded in a shader, but blending happens in sRGB space.
+    pub fn prefer_srgb(mut self, prefer: bool) -> Self {
+        self.prefer_srgb = prefer;
+        self
+    }
+
+    /// Makes window's transparent pixels blend with whatever is behind it
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Sets how many frames can wait for a render thread before rendering blocks, 2 by default
+    ///
+    /// Lower values reduce input latency when frames are produced faster than they are presented.
+    pub fn max_frames_in_flight(mut self, frames: usize) -> Self {
+        self.max_frames_in_flight = frames.max(1);
+        self
+    }
+
+    /// Measures GPU time of every frame, reported in [`FrameStats`](super::stats::FrameStats), if adapter supports it
+    ///
+    /// Reading measurements makes render thread wait for GPU to finish each frame.
+    pub fn gpu_timing(mut self, enabled: bool) -> Self {
+        self.gpu_timing = enabled;
+        self
+    }
+
+    /// Returns optional device features which are requested if adapter supports them
+    pub(crate) fn wgpu_features(&self) -> wgpu::Features {
+        if self.gpu_timing {
+            wgpu::Features::TIMESTAMP_QUERY
+        } else {
+            wgpu::Features::empty()
+        }
+    }
+
+    pub(crate) fn wgpu_backends(&self) -> wgpu::Backends {
+        match self.backends {
+            Backends::All => wgpu::Backends::all(),
+            Backends::Primary => wgpu::Backends::PRIMARY,
+            Backends::Vulkan => wgpu::Backends::VULKAN,
+            Backends::Gl => wgpu::Backends::GL,
+            Backends::Metal => wgpu::Backends::METAL,
+            Backends::Dx12 => wgpu::Backends::DX12,
+        }
+    }
+
+    pub(crate) fn wgpu_power_preference(&self) -> wgpu::PowerPreference {
+        match self.power_preference {
+            PowerPreference::Default => wgpu::PowerPreference::default(),
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+
+    /// Picks requested present mode, falling back to vsync if it is not supported
+    pub(crate) fn choose_present_mode(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let requested = match self.present_mode {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        };
+        if supported.contains(&requested) {
+            requested
+        } else {
+            wgpu::PresentMode::Fifo
+        }
+    }
+
+    /// Picks surface format, which is sRGB if it is preferred and supported
+    pub(crate) fn choose_surface_format(
+        &self,
+        supported: &[wgpu::TextureFormat],
+    ) -> wgpu::TextureFormat {
+        supported
+            .iter()
+            .copied()
+            .find(|format| !self.prefer_srgb || format.is_srgb())
+            .unwrap_or(supported[0])
+    }
+
+    /// Picks alpha mode, which lets a window be transparent if requested and supported
+    ///
+    /// Premultiplied mode matches colors left in a target by alpha blending, so it's preferred.
+    pub(crate) fn choose_alpha_mode(
+        &self,
+        supported: &[wgpu::CompositeAlphaMode],
+    ) -> wgpu::CompositeAlphaMode {
+        let preferred: &[_] = if self.transparent {
+            &[
+                wgpu::CompositeAlphaMode::PreMultiplied,
+                wgpu::CompositeAlphaMode::PostMultiplied,
+                wgpu::CompositeAlphaMode::Inherit,
+            ]
+        } else {
+            &[wgpu::CompositeAlphaMode::Opaque]
+        };
+        preferred
+            .iter()
+            .copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(supported[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PresentMode, RendererConfig};
+
+    #[test]
+    fn test_unsupported_present_mode_falls_back_to_vsync() {
+        let config = RendererConfig::default().present_mode(PresentMode::Mailbox);
+        assert_eq!(
+            wgpu::PresentMode::Fifo,
+            config.choose_present_mode(&[wgpu::PresentMode::Fifo, wgpu::PresentMode::Immediate])
+        );
+        assert_eq!(
+            wgpu::PresentMode::Mailbox,
+            config.choose_present_mode(&[wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox])
+        );
+    }
+
+    #[test]
+    fn test_surface_format_prefers_srgb() {
+        let formats = [
+            wgpu::TextureFormat::Bgra8Unorm,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+        ];
+        assert_eq!(
+            wgpu::TextureFormat::Bgra8Unorm,
+            RendererConfig::default()
+                .prefer_srgb(false)
+                .choose_surface_format(&formats)
+        );
+        assert_eq!(
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            RendererConfig::default().choose_surface_format(&formats)
+        );
+        assert_eq!(
+            wgpu::TextureFormat::Bgra8Unorm,
+            RendererConfig::default().choose_surface_format(&formats[..1])
+        );
+    }
+
+    #[test]
+    fn test_transparent_alpha_mode_falls_back_to_supported() {
+        let config = RendererConfig::default().transparent(true);
+        assert_eq!(
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            config.choose_alpha_mode(&[
+                wgpu::CompositeAlphaMode::Opaque,
+                wgpu::CompositeAlphaMode::PostMultiplied
+            ])
+        );
+        assert_eq!(
+            wgpu::CompositeAlphaMode::Opaque,
+            config.choose_alpha_mode(&[wgpu::CompositeAlphaMode::Opaque])
+        );
+    }
+}
@@ -0,0 +1,22 @@
+// param(0) - brightness threshold, param(1) - intensity, param(2) - radius in pixels
+fn bright_part(uv: vec2<f32>) -> vec3<f32> {
+    let color = sample_input(uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    return color * step(param(0), brightness);
+}
+
+@fragment
+fn fs_main(in: PostVertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    let texel = max(param(2), 1.0) / post.resolution;
+    var glow = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var x = -3; x <= 3; x++) {
+        for (var y = -3; y <= 3; y++) {
+            let weight = 1.0 / (1.0 + f32(x * x + y * y));
+            glow += bright_part(in.uv + vec2<f32>(f32(x), f32(y)) * texel) * weight;
+            total += weight;
+        }
+    }
+    return vec4<f32>(color.rgb + glow / total * param(1), color.a);
+}
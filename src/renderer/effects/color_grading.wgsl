@@ -0,0 +1,10 @@
+// param(0) - brightness, param(1) - contrast, param(2) - saturation
+@fragment
+fn fs_main(in: PostVertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    var rgb = color.rgb + vec3<f32>(param(0));
+    rgb = (rgb - vec3<f32>(0.5)) * param(1) + vec3<f32>(0.5);
+    let luminance = dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3<f32>(luminance), rgb, param(2));
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
@@ -0,0 +1,32 @@
+struct PostUniform {
+    resolution: vec2<f32>,
+    params: array<vec4<f32>, 4>,
+};
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> post: PostUniform;
+
+struct PostVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_post(@builtin(vertex_index) index: u32) -> PostVertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: PostVertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn param(index: i32) -> f32 {
+    return post.params[index / 4][index % 4];
+}
+
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(input_texture, input_sampler, uv);
+}
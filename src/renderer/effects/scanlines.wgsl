@@ -0,0 +1,8 @@
+// param(0) - intensity, param(1) - line spacing in pixels
+@fragment
+fn fs_main(in: PostVertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    let row = floor(in.uv.y * post.resolution.y / max(param(1), 1.0));
+    let darken = select(0.0, param(0), row % 2.0 == 1.0);
+    return vec4<f32>(color.rgb * (1.0 - darken), color.a);
+}
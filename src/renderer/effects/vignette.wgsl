@@ -0,0 +1,8 @@
+// param(0) - strength, param(1) - radius
+@fragment
+fn fs_main(in: PostVertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_input(in.uv);
+    let distance = length(in.uv - vec2<f32>(0.5, 0.5)) * 1.41421356;
+    let falloff = smoothstep(param(1), 1.0, distance) * param(0);
+    return vec4<f32>(color.rgb * (1.0 - falloff), color.a);
+}
@@ -0,0 +1,154 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+struct Counters {
+    submitted: u64,
+    finished: u64,
+    last_presented: Option<u64>,
+    closed: bool,
+}
+
+/// Frame counters shared by renderer and render thread, limiting number of frames in flight
+#[derive(Debug)]
+pub(crate) struct FramePacing {
+    counters: Mutex<Counters>,
+    changed: Condvar,
+    max_in_flight: u64,
+}
+
+impl FramePacing {
+    pub(crate) fn new(max_in_flight: usize) -> Arc<FramePacing> {
+        Arc::new(FramePacing {
+            counters: Mutex::default(),
+            changed: Condvar::new(),
+            max_in_flight: max_in_flight.max(1) as u64,
+        })
+    }
+
+    /// Waits until another frame can be in flight, returning its index
+    pub(crate) fn begin_frame(&self) -> u64 {
+        let mut counters = self
+            .wait_while(|counters| counters.submitted - counters.finished >= self.max_in_flight);
+        let index = counters.submitted;
+        counters.submitted += 1;
+        index
+    }
+
+    /// Marks frame as handled by render thread, either presented or skipped
+    pub(crate) fn finish_frame(&self, index: u64, presented: bool) {
+        let mut counters = self.lock();
+        counters.finished = counters.finished.max(index + 1);
+        if presented {
+            counters.last_presented = Some(index);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Stops any waiting, as no more frames will be finished
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.changed.notify_all();
+    }
+
+    pub(crate) fn last_presented(&self) -> Option<u64> {
+        self.lock().last_presented
+    }
+
+    fn is_finished(&self, index: u64) -> bool {
+        let counters = self.lock();
+        counters.closed || counters.finished > index
+    }
+
+    fn wait_for(&self, index: u64) {
+        drop(self.wait_while(|counters| counters.finished <= index));
+    }
+
+    /// Blocks while a condition holds and render thread is running
+    fn wait_while(&self, condition: impl Fn(&Counters) -> bool) -> MutexGuard<'_, Counters> {
+        self.changed
+            .wait_while(self.lock(), |counters| {
+                !counters.closed && condition(counters)
+            })
+            .unwrap()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap()
+    }
+}
+
+/// Closes frame pacing once dropped, also when render thread unwinds
+pub(crate) struct CloseOnDrop(pub(crate) Arc<FramePacing>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Handle to a frame submitted with [`Renderer::render`](super::Renderer::render)
+#[derive(Debug, Clone)]
+pub struct FrameHandle {
+    pub(crate) index: u64,
+    pub(crate) pacing: Arc<FramePacing>,
+}
+
+impl FrameHandle {
+    /// Returns index of the frame, counting from 0 for the first rendered one
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns whether render thread has finished the frame, either presenting or skipping it
+    pub fn is_finished(&self) -> bool {
+        self.pacing.is_finished(self.index)
+    }
+
+    /// Blocks until render thread finishes the frame, or stops
+    pub fn wait(&self) {
+        self.pacing.wait_for(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::{CloseOnDrop, FrameHandle, FramePacing};
+
+    #[test]
+    fn test_begin_frame_waits_for_frames_in_flight() {
+        let pacing = FramePacing::new(2);
+        assert_eq!(0, pacing.begin_frame());
+        assert_eq!(1, pacing.begin_frame());
+
+        let waiting = {
+            let pacing = Arc::clone(&pacing);
+            thread::spawn(move || pacing.begin_frame())
+        };
+        pacing.finish_frame(0, true);
+        assert_eq!(2, waiting.join().unwrap());
+        assert_eq!(Some(0), pacing.last_presented());
+    }
+
+    #[test]
+    fn test_frame_handle_is_finished_when_skipped_or_closed() {
+        let pacing = FramePacing::new(1);
+        let first = FrameHandle {
+            index: pacing.begin_frame(),
+            pacing: Arc::clone(&pacing),
+        };
+        assert!(!first.is_finished());
+        pacing.finish_frame(first.index(), false);
+        first.wait();
+        assert_eq!(None, pacing.last_presented());
+
+        let second = FrameHandle {
+            index: pacing.begin_frame(),
+            pacing: Arc::clone(&pacing),
+        };
+        drop(CloseOnDrop(Arc::clone(&pacing)));
+        second.wait();
+        assert!(second.is_finished());
+    }
+}
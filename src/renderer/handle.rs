@@ -0,0 +1,170 @@
+use std::sync::{mpsc, Arc};
+#[cfg(feature = "serde")]
+use std::sync::{Mutex, MutexGuard};
+
+use cgmath::Vector2;
+
+use super::{
+    backend::SpriteFactory, render_thread::RenderThreadMessage, sprite::Sprite,
+    texture_ref::TextureRefManager, TextureData,
+};
+
+#[cfg(feature = "serde")]
+use super::command_log::CommandLog;
+
+/// Cheaply cloneable handle which manages sprites of a [`Renderer`](super::Renderer) from any
+/// thread, e.g. asset loading workers
+///
+/// Sprites are created in the order handles send them, before frames rendered afterwards.
+/// Once renderer is dropped, sprites can still be created, but they are never loaded.
+#[derive(Clone)]
+pub struct RendererHandle {
+    tx: mpsc::Sender<RenderThreadMessage>,
+    texture_ref_manager: Arc<TextureRefManager>,
+    #[cfg(feature = "serde")]
+    command_log: Arc<Mutex<Option<CommandLog>>>,
+}
+
+impl RendererHandle {
+    pub(crate) fn new(tx: mpsc::Sender<RenderThreadMessage>) -> RendererHandle {
+        RendererHandle {
+            tx,
+            texture_ref_manager: Arc::new(TextureRefManager::new()),
+            #[cfg(feature = "serde")]
+            command_log: Arc::default(),
+        }
+    }
+
+    /// Creates a sprite, loading data provided in a param to it
+    pub fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        let sprite = Sprite {
+            texture: self.texture_ref_manager.next(),
+            size: data.size(),
+        };
+        let bytes = data.data();
+        #[cfg(feature = "serde")]
+        let _log = self.log_command(|log| log.log_sprite(&sprite, bytes.clone()));
+        self.load_texture(&sprite, bytes);
+        sprite
+    }
+
+    /// Replaces sprite's data, which can have a different size
+    ///
+    /// Frames rendered before the update keep drawing old data.
+    pub fn update_sprite(&self, sprite: &mut Sprite, data: impl TextureData) {
+        self.update_sprite_data(sprite, data.size(), data.data());
+    }
+
+    /// Replaces sprite's data with already converted RGBA bytes
+    pub(crate) fn update_sprite_data(
+        &self,
+        sprite: &mut Sprite,
+        size: Vector2<u32>,
+        bytes: Vec<u8>,
+    ) {
+        sprite.size = size;
+        #[cfg(feature = "serde")]
+        let _log = self.log_command(|log| log.log_sprite_update(sprite, bytes.clone()));
+        self.load_texture(sprite, bytes);
+    }
+
+    /// Frees sprite's texture once frames rendered before are finished
+    pub fn unload_sprite(&self, sprite: Sprite) {
+        #[cfg(feature = "serde")]
+        let _log = self.log_command(|log| log.log_sprite_unload(&sprite));
+        let _ = self
+            .tx
+            .send(RenderThreadMessage::UnloadTexture(sprite.texture));
+    }
+
+    fn load_texture(&self, sprite: &Sprite, data: Vec<u8>) {
+        // Sending fails only after renderer is dropped, when sprites are never drawn anyway
+        let _ = self.tx.send(RenderThreadMessage::LoadTexture(
+            sprite.texture,
+            data,
+            sprite.size,
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn command_log(&self) -> &Mutex<Option<CommandLog>> {
+        &self.command_log
+    }
+
+    /// Records a command if log is enabled, returning the log still locked
+    ///
+    /// Lock should be held until the command is sent to render thread, so commands sent from
+    /// many threads are logged in the same order render thread receives them.
+    #[cfg(feature = "serde")]
+    pub(crate) fn log_command(
+        &self,
+        log_command: impl FnOnce(&mut CommandLog),
+    ) -> MutexGuard<'_, Option<CommandLog>> {
+        let mut log = self.command_log.lock().unwrap();
+        if let Some(log) = log.as_mut() {
+            log_command(log);
+        }
+        log
+    }
+}
+
+impl SpriteFactory for RendererHandle {
+    fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        RendererHandle::create_sprite(self, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use image::Rgba;
+
+    use crate::renderer::{
+        config::RendererConfig,
+        sprite::Sprite,
+        testing::{scenes::Pixel, skip_without_adapter},
+        Color, Renderer,
+    };
+
+    use super::RendererHandle;
+
+    #[test]
+    fn test_handle_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<RendererHandle>();
+    }
+
+    #[test]
+    fn test_sprites_managed_from_another_thread() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 2), RendererConfig::default()))
+        else {
+            return;
+        };
+        let handle = renderer.handle();
+        let mut sprite = thread::spawn(move || handle.create_sprite(Pixel([255, 0, 0, 255])))
+            .join()
+            .unwrap();
+        let render = |sprite: &Sprite| {
+            let frame = renderer.capture_next_frame();
+            renderer.render(|commands| {
+                commands.set_clear_color(Color::BLUE);
+                commands.draw(sprite).at((0, 0));
+            });
+            *frame.recv().unwrap().get_pixel(0, 1)
+        };
+
+        assert_eq!(Rgba([255, 0, 0, 255]), render(&sprite));
+        renderer.update_sprite(&mut sprite, Pixel([0, 255, 0, 255]));
+        assert_eq!(Rgba([0, 255, 0, 255]), render(&sprite));
+        let texture = sprite.texture;
+        renderer.handle().unload_sprite(sprite);
+        // Blits of unloaded sprites are skipped
+        let unloaded = Sprite {
+            texture,
+            size: (1, 1).into(),
+        };
+        assert_eq!(Rgba([0, 0, 255, 255]), render(&unloaded));
+    }
+}
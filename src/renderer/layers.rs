@@ -0,0 +1,132 @@
+use std::iter;
+
+use super::{BlitCommand, Stencil};
+
+/// Sequence of blits that has to be drawn together - a single blit or a whole mask scope
+struct Unit {
+    layer: i16,
+    blits: Vec<BlitCommand>,
+}
+
+/// Stably sorts blits by their layers
+///
+/// Mask scopes are kept together and are placed at the lowest layer of their content, while
+/// blits inside them are sorted the same way.
+pub(crate) fn sort_by_layer(blits: Vec<BlitCommand>) -> Vec<BlitCommand> {
+    sort_level(blits, 0)
+}
+
+fn sort_level(blits: Vec<BlitCommand>, depth: u8) -> Vec<BlitCommand> {
+    let mut units = vec![];
+    let mut blits = blits.into_iter().peekable();
+    while let Some(next) = blits.peek() {
+        if next.stencil == Stencil::Test(depth) {
+            let blit = blits.next().expect("Should be peeked");
+            units.push(Unit {
+                layer: blit.layer,
+                blits: vec![blit],
+            });
+            continue;
+        }
+
+        let mut scope: Vec<_> =
+            iter::from_fn(|| blits.next_if(|blit| blit.stencil == Stencil::Write(depth))).collect();
+        let content: Vec<_> =
+            iter::from_fn(|| blits.next_if(|blit| is_nested(blit.stencil, depth))).collect();
+        let content = sort_level(content, depth.saturating_add(1));
+        let layer = content
+            .iter()
+            .map(|blit| blit.layer)
+            .min()
+            .or_else(|| scope.iter().map(|blit| blit.layer).min())
+            .unwrap_or_default();
+        scope.extend(content);
+        scope.extend(iter::from_fn(|| {
+            blits.next_if(|blit| blit.stencil == Stencil::Erase(depth.saturating_add(1)))
+        }));
+
+        if scope.is_empty() {
+            // Not expected for commands built by `RenderCommands`, but keeps blits flowing anyway
+            scope.extend(blits.next());
+        }
+        units.push(Unit {
+            layer,
+            blits: scope,
+        });
+    }
+
+    units.sort_by_key(|unit| unit.layer);
+    units.into_iter().flat_map(|unit| unit.blits).collect()
+}
+
+/// Returns whether blit with a given stencil belongs to a content of a mask scope at `depth`
+fn is_nested(stencil: Stencil, depth: u8) -> bool {
+    let depth = depth as u16;
+    match stencil {
+        Stencil::Test(d) | Stencil::Write(d) => d as u16 > depth,
+        Stencil::Erase(d) => d as u16 > depth + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{
+        sprite::Sprite, texture_ref::TextureRefManager, RenderCommands, Stencil,
+    };
+
+    use super::sort_by_layer;
+
+    fn sprite() -> Sprite {
+        Sprite {
+            texture: TextureRefManager::new().next(),
+            size: (8, 8).into(),
+        }
+    }
+
+    #[test]
+    fn test_sort_is_stable() {
+        let mut commands = RenderCommands::default();
+        commands.draw(&sprite()).at((0, 0)).on_layer(1);
+        commands.draw(&sprite()).at((1, 0)).on_layer(-1);
+        commands.draw(&sprite()).at((2, 0)).on_layer(1);
+        commands.draw(&sprite()).at((3, 0));
+
+        let positions: Vec<_> = sort_by_layer(commands.blits)
+            .iter()
+            .map(|blit| blit.position.x)
+            .collect();
+        assert_eq!(vec![1, 3, 0, 2], positions);
+    }
+
+    #[test]
+    fn test_mask_scope_is_sorted_as_a_unit() {
+        let mut commands = RenderCommands::default();
+        commands.draw(&sprite()).at((0, 0)).on_layer(2);
+        commands.with_mask(
+            |mask| {
+                mask.draw(&sprite()).at((1, 0));
+            },
+            |content| {
+                content.draw(&sprite()).at((2, 0)).on_layer(3);
+                content.draw(&sprite()).at((3, 0)).on_layer(1);
+            },
+        );
+        commands.draw(&sprite()).at((4, 0)).on_layer(0);
+
+        let sorted: Vec<_> = sort_by_layer(commands.blits)
+            .iter()
+            .map(|blit| (blit.position.x, blit.stencil))
+            .collect();
+        assert_eq!(
+            vec![
+                (4, Stencil::Test(0)),
+                (1, Stencil::Write(0)),
+                (3, Stencil::Test(1)),
+                (2, Stencil::Test(1)),
+                (1, Stencil::Erase(1)),
+                (0, Stencil::Test(0)),
+            ],
+            sorted
+        );
+    }
+}
@@ -1,90 +1,458 @@
+pub mod backend;
+pub mod color;
+#[cfg(feature = "serde")]
+pub mod command_log;
+pub mod config;
+pub mod frame;
+pub mod handle;
+mod layers;
+pub mod post_effect;
+mod recording;
+pub mod rect;
 mod render_thread;
+pub mod software;
 pub mod sprite;
+pub mod stats;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod texture_ref;
 
 use std::{
-    sync::mpsc::{self},
+    any::Any,
+    error::Error,
+    fmt, io, panic,
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
     thread, vec,
 };
 
 use cgmath::Vector2;
+use image::RgbaImage;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
+pub use self::color::Color;
+
 use self::{
+    config::RendererConfig,
+    frame::{FrameHandle, FramePacing},
+    handle::RendererHandle,
+    post_effect::PostEffect,
+    recording::Recording,
+    rect::Rect,
     render_thread::{RenderThreadMessage, RendererThread},
     sprite::Sprite,
-    texture_ref::{TextureRef, TextureRefManager},
+    stats::FrameStats,
+    texture_ref::TextureRef,
 };
 
 /// Trait which describes windows that can be used with renderer
 pub trait CompatibleWindow: HasRawWindowHandle + HasRawDisplayHandle {}
 impl<T: HasRawWindowHandle + HasRawDisplayHandle> CompatibleWindow for T {}
 
-/// An RGBA color
-pub type Color = wgpu::Color;
+/// Size of a single layer of a texture array used by [`TextureBatching::TextureArray`]
+pub const TEXTURE_ARRAY_LAYER_SIZE: u32 = 256;
+
+/// Describes how blits of different sprites are grouped into draw calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureBatching {
+    /// Each sprite has its own texture, so only consecutive blits of the same sprite share a draw call
+    #[default]
+    PerTexture,
+    /// Sprites are additionally copied into layers of a 2D texture array, so blits of
+    /// different sprites can share a draw call, even if they are interleaved
+    ///
+    /// Sprites larger than [`TEXTURE_ARRAY_LAYER_SIZE`] in any dimension, or ones that do not fit
+    /// into a device's array layer limit, fall back to [`TextureBatching::PerTexture`].
+    TextureArray,
+}
+
+/// Error which occurred on a render thread, after which rendering can still continue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// GPU ran out of memory while acquiring a frame, so the frame was dropped
+    OutOfMemory,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::OutOfMemory => write!(f, "out of GPU memory"),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// Panic which stopped a render thread, holding its payload
+pub struct RenderThreadPanic(Box<dyn Any + Send + 'static>);
+
+impl RenderThreadPanic {
+    /// Returns panic's message, if it was a string
+    pub fn message(&self) -> Option<&str> {
+        self.0
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| self.0.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// Returns panic's payload, e.g. to resume unwinding with it
+    pub fn into_payload(self) -> Box<dyn Any + Send + 'static> {
+        self.0
+    }
+}
+
+impl fmt::Debug for RenderThreadPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RenderThreadPanic")
+            .field(&self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for RenderThreadPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "render thread panicked: {}", message),
+            None => write!(f, "render thread panicked"),
+        }
+    }
+}
+
+impl Error for RenderThreadPanic {}
 
 /// Allows rendering 2D pixel-perfect graphics on a compatible window
 pub struct Renderer {
     renderer_thread_tx: mpsc::Sender<RenderThreadMessage>,
-    texture_ref_manager: TextureRefManager,
+    errors_rx: mpsc::Receiver<RenderError>,
+    render_thread: Option<thread::JoinHandle<()>>,
+    frame_pacing: Arc<FramePacing>,
+    stats: Arc<Mutex<Option<FrameStats>>>,
+    recording: Option<Recording>,
+    /// Whether frames of a target can be copied, which recording requires
+    can_capture: bool,
+    handle: RendererHandle,
 }
 
 impl Renderer {
     /// Creates renderer compatible with provided window, for given size
     pub fn compatible_with<T>(window: impl CompatibleWindow, size: (u32, u32)) -> Renderer {
-        let renderer_thread = RendererThread::compatible_with(window, size);
+        Self::with_config(window, size, RendererConfig::default())
+    }
+
+    /// Creates renderer compatible with provided window, for given size, set up as configured
+    pub fn with_config(
+        window: impl CompatibleWindow,
+        size: (u32, u32),
+        config: RendererConfig,
+    ) -> Renderer {
+        let renderer_thread = RendererThread::compatible_with(window, size, &config);
+        Self::spawn(renderer_thread, &config)
+    }
+
+    /// Creates renderer drawing to an offscreen texture instead of a window
+    ///
+    /// Adapter is chosen according to a config, falling back to any available one. Returns `None`
+    /// if there is no adapter at all. Frames can be read with [`Renderer::capture_next_frame`].
+    #[cfg(any(test, feature = "testing"))]
+    pub fn headless(size: (u32, u32), config: RendererConfig) -> Option<Renderer> {
+        let renderer_thread = RendererThread::headless(size, &config)?;
+        Some(Self::spawn(renderer_thread, &config))
+    }
+
+    fn spawn(renderer_thread: RendererThread, config: &RendererConfig) -> Renderer {
+        let can_capture = renderer_thread.can_capture();
         let (tx, rx) = mpsc::channel();
-        thread::spawn(|| renderer_thread.run(rx));
-        let texture_ref_manager = TextureRefManager::new();
+        let (errors_tx, errors_rx) = mpsc::channel();
+        let frame_pacing = FramePacing::new(config.max_frames_in_flight);
+        let stats = Arc::default();
+        let thread_frame_pacing = Arc::clone(&frame_pacing);
+        let thread_stats = Arc::clone(&stats);
+        let render_thread =
+            thread::spawn(|| renderer_thread.run(rx, errors_tx, thread_frame_pacing, thread_stats));
+        let handle = RendererHandle::new(tx.clone());
         Renderer {
             renderer_thread_tx: tx,
-            texture_ref_manager,
+            errors_rx,
+            render_thread: Some(render_thread),
+            frame_pacing,
+            stats,
+            recording: None,
+            can_capture,
+            handle,
         }
     }
 
+    /// Stops render thread after it handles already sent commands, waiting for it to finish
+    ///
+    /// Unlike dropping the renderer, it reports whether render thread panicked.
+    pub fn shutdown(mut self) -> Result<(), RenderThreadPanic> {
+        self.stop_render_thread()
+    }
+
+    /// Returns errors reported by a render thread since the last call, without blocking
+    pub fn errors(&self) -> mpsc::TryIter<'_, RenderError> {
+        self.errors_rx.try_iter()
+    }
+
     /// Notifies renderer about window's resize
+    ///
+    /// Zero sized windows, e.g. minimized ones, are accepted, frames are skipped until next resize.
     pub fn resize(&self, size: Vector2<u32>) {
+        #[cfg(feature = "serde")]
+        let _log = self.handle.log_command(|log| log.log_resize(size));
         self.renderer_thread_tx
             .send(RenderThreadMessage::Resize(size))
             .unwrap();
     }
 
     /// Render things described by a callback to a window
-    pub fn render(&self, callback: impl FnOnce(&mut RenderCommands)) {
-        let mut target = RenderCommands {
-            clear_color: Some(Color::BLACK),
-            blits: vec![],
-        };
+    ///
+    /// Blocks while [`RendererConfig::max_frames_in_flight`] frames are waiting for render thread.
+    pub fn render(&self, callback: impl FnOnce(&mut RenderCommands)) -> FrameHandle {
+        let mut target = RenderCommands::default();
         callback(&mut target);
+        self.submit(target)
+    }
+
+    /// Renders things described by callbacks, each running on its own thread
+    ///
+    /// Sub-lists are merged in callbacks' order as if by [`RenderCommands::append`], so blits of
+    /// later callbacks are drawn over earlier ones on the same layer. Frame is cleared with
+    /// a color set by the first callback.
+    pub fn render_parallel<F>(&self, callbacks: impl IntoIterator<Item = F>) -> FrameHandle
+    where
+        F: FnOnce(&mut RenderCommands) + Send,
+    {
+        let fragments: Vec<_> = thread::scope(|scope| {
+            let threads: Vec<_> = callbacks
+                .into_iter()
+                .map(|callback| {
+                    scope.spawn(|| {
+                        let mut fragment = RenderCommands::default();
+                        callback(&mut fragment);
+                        fragment
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        let mut fragments = fragments.into_iter();
+        let mut target = fragments.next().unwrap_or_default();
+        for fragment in fragments {
+            target.append(fragment);
+        }
+        self.submit(target)
+    }
+
+    fn submit(&self, target: RenderCommands) -> FrameHandle {
+        let index = self.frame_pacing.begin_frame();
+        #[cfg(feature = "serde")]
+        let _log = self.handle.log_command(|log| log.log_render(&target));
         self.renderer_thread_tx
-            .send(RenderThreadMessage::Render(target))
+            .send(RenderThreadMessage::Render(target, index))
             .unwrap();
+        FrameHandle {
+            index,
+            pacing: Arc::clone(&self.frame_pacing),
+        }
     }
 
-    /// Creates a sprite, loading data provided in a param to it
-    pub fn create_sprite(&self, data: impl TextureData) -> Sprite {
-        let texture_ref = self.texture_ref_manager.next();
+    /// Requests a copy of the next presented frame
+    ///
+    /// Receiver gets disconnected without an image if a window's surface does not support copying
+    /// its frames, or if render thread stops before the frame is presented.
+    pub fn capture_next_frame(&self) -> mpsc::Receiver<RgbaImage> {
+        let (tx, rx) = mpsc::channel();
         self.renderer_thread_tx
-            .send(RenderThreadMessage::LoadTexture(
-                texture_ref,
-                data.data(),
-                data.size(),
-            ))
+            .send(RenderThreadMessage::CaptureNextFrame(tx))
             .unwrap();
-        Sprite {
-            texture: texture_ref,
-            size: data.size(),
+        rx
+    }
+
+    /// Saves the next presented frame to a file, in a format deduced from its extension
+    ///
+    /// Frame is saved on a background thread, so it does not block rendering the frame. Returned
+    /// handle can be joined to learn whether saving succeeded.
+    pub fn save_screenshot(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> thread::JoinHandle<image::ImageResult<()>> {
+        let frame = self.capture_next_frame();
+        let path = path.as_ref().to_owned();
+        thread::spawn(move || {
+            let image = frame.recv().map_err(|_| {
+                image::ImageError::IoError(io::Error::other("frame could not be captured"))
+            })?;
+            image.save(path)
+        })
+    }
+
+    /// Starts recording presented frames to an animated GIF or APNG file, at a given frame rate
+    ///
+    /// Format is deduced from file's extension: `.gif`, or `.png` / `.apng`. Frames are encoded on
+    /// a background thread, but capturing them makes render thread wait for GPU to finish each
+    /// recorded frame. Frames with other size than the first one, e.g. after resizing a window,
+    /// are skipped. Fails if frames of a window's surface cannot be copied.
+    pub fn start_recording(&mut self, path: impl AsRef<Path>, fps: u32) -> io::Result<()> {
+        if self.recording.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "recording is already in progress",
+            ));
+        }
+        if !self.can_capture {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "frames of this window's surface cannot be copied",
+            ));
+        }
+        let (recording, sampler) = Recording::start(path.as_ref(), fps)?;
+        self.renderer_thread_tx
+            .send(RenderThreadMessage::StartRecording(sampler))
+            .unwrap();
+        self.recording = Some(recording);
+        Ok(())
+    }
+
+    /// Stops recording and waits until all recorded frames are saved
+    ///
+    /// Returns `None` if nothing was being recorded.
+    pub fn stop_recording(&mut self) -> Option<image::ImageResult<()>> {
+        let recording = self.recording.take()?;
+        // Render thread which has already stopped drops the recording as well
+        let _ = self
+            .renderer_thread_tx
+            .send(RenderThreadMessage::StopRecording);
+        Some(recording.finish())
+    }
+
+    /// Starts recording sprites, resizes and frames sent to this renderer into a [`CommandLog`](command_log::CommandLog)
+    ///
+    /// Restarts recording if it was already in progress.
+    #[cfg(feature = "serde")]
+    pub fn record_commands(&self) {
+        *self.handle.command_log().lock().unwrap() = Some(Default::default());
+    }
+
+    /// Stops recording commands, returning log recorded since [`Renderer::record_commands`]
+    #[cfg(feature = "serde")]
+    pub fn stop_recording_commands(&self) -> Option<command_log::CommandLog> {
+        self.handle.command_log().lock().unwrap().take()
+    }
+
+    /// Returns statistics of the last presented frame, if any
+    pub fn stats(&self) -> Option<FrameStats> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Returns index of the last frame presented on a window, if any
+    ///
+    /// Frames skipped e.g. while a window is minimized are never presented.
+    pub fn last_presented_frame(&self) -> Option<u64> {
+        self.frame_pacing.last_presented()
+    }
+
+    /// Sets chain of full-screen effects applied, in order, to every rendered frame
+    ///
+    /// Passing an empty vector disables post processing.
+    pub fn set_post_effects(&self, effects: Vec<PostEffect>) {
+        self.renderer_thread_tx
+            .send(RenderThreadMessage::SetPostEffects(effects))
+            .unwrap();
+    }
+
+    /// Changes how blits are grouped into draw calls
+    pub fn set_texture_batching(&self, batching: TextureBatching) {
+        self.renderer_thread_tx
+            .send(RenderThreadMessage::SetTextureBatching(batching))
+            .unwrap();
+    }
+
+    /// Returns handle which can create, update and unload sprites from other threads
+    pub fn handle(&self) -> RendererHandle {
+        self.handle.clone()
+    }
+
+    /// Creates a sprite, loading data provided in a param to it
+    pub fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        self.handle.create_sprite(data)
+    }
+
+    /// Replaces sprite's data, which can have a different size
+    pub fn update_sprite(&self, sprite: &mut Sprite, data: impl TextureData) {
+        self.handle.update_sprite(sprite, data)
+    }
+
+    /// Frees sprite's texture once frames rendered before are finished
+    pub fn unload_sprite(&self, sprite: Sprite) {
+        self.handle.unload_sprite(sprite)
+    }
+
+    fn stop_render_thread(&mut self) -> Result<(), RenderThreadPanic> {
+        let Some(render_thread) = self.render_thread.take() else {
+            return Ok(());
+        };
+        // Sending fails only if render thread has already stopped, which join reports
+        let _ = self.renderer_thread_tx.send(RenderThreadMessage::Shutdown);
+        render_thread.join().map_err(RenderThreadPanic)
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        let _ = self.stop_render_thread();
+        if let Some(recording) = self.recording.take() {
+            let _ = recording.finish();
         }
     }
 }
 
 /// Describes a single blit (sprite drawing) operation
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlitCommand {
+    #[cfg_attr(feature = "serde", serde(rename = "sprite"))]
     pub(crate) texture_id: TextureRef,
     pub(crate) position: Vector2<u32>,
     pub(crate) size: Vector2<u32>,
     pub(crate) color: Color,
+    pub(crate) clip: Option<Rect>,
+    pub(crate) stencil: Stencil,
+    pub(crate) layer: i16,
+}
+
+/// Describes how a blit interacts with a stencil buffer used for masking
+///
+/// Stencil value of a pixel is the number of masks covering it, so every variant compares
+/// it with a given mask depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Stencil {
+    /// Draws sprite where stencil equals given depth
+    Test(u8),
+    /// Increments stencil where it equals given depth, without drawing sprite
+    Write(u8),
+    /// Decrements stencil where it equals given depth, without drawing sprite
+    Erase(u8),
+}
+
+impl Stencil {
+    /// Returns stencil of a blit recorded at mask depth 0, when it's drawn inside a given one
+    fn nested_in(self, depth: u8) -> Stencil {
+        let nested = |own: u8| own.checked_add(depth).expect("Too many nested masks");
+        match self {
+            Stencil::Test(own) => Stencil::Test(nested(own)),
+            Stencil::Write(own) => Stencil::Write(nested(own)),
+            Stencil::Erase(own) => Stencil::Erase(nested(own)),
+        }
+    }
 }
 
 impl BlitCommand {
@@ -99,13 +467,65 @@ impl BlitCommand {
         self.color = color;
         self
     }
+
+    /// Moves a sprite to a given layer
+    ///
+    /// Sprites on higher layers are drawn over ones on lower layers, regardless of the order
+    /// they were drawn in. Sprites on the same layer are drawn in submission order. Default layer is 0.
+    ///
+    /// Everything drawn by [`RenderCommands::with_mask`] is ordered as one unit, placed at the
+    /// lowest layer of its content, while content sprites are ordered by layers among themselves.
+    /// So a sprite drawn outside of the mask on a higher layer than that covers all of the
+    /// content, even content sprites on even higher layers.
+    pub fn on_layer(&mut self, layer: i16) -> &mut Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Returns ID of a drawn sprite, as returned by [`Sprite::id`]
+    pub fn sprite_id(&self) -> usize {
+        self.texture_id.0
+    }
+
+    /// Returns sprite's screen position
+    pub fn position(&self) -> Vector2<u32> {
+        self.position
+    }
+
+    /// Returns sprite's size
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    /// Returns sprite's color
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Returns rectangle sprite is clipped to, if any
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    /// Returns sprite's layer
+    pub fn layer(&self) -> i16 {
+        self.layer
+    }
+
+    /// Returns whether sprite defines a mask, instead of being drawn
+    pub fn is_mask(&self) -> bool {
+        !matches!(self.stencil, Stencil::Test(_))
+    }
 }
 
 /// Allows to define render operations
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RenderCommands {
     clear_color: Option<Color>,
     blits: Vec<BlitCommand>,
+    clip_stack: Vec<Rect>,
+    stencil: Stencil,
 }
 
 impl RenderCommands {
@@ -121,12 +541,97 @@ impl RenderCommands {
             position: (0, 0).into(),
             size: sprite.size,
             color: Color::WHITE,
+            clip: self.clip_stack.last().copied(),
+            stencil: self.stencil,
+            layer: 0,
         };
         self.blits.push(blit_command);
         self.blits
             .last_mut()
             .expect("Should be inserted by last command")
     }
+
+    /// Restricts drawing of subsequent sprites to a given rectangle
+    ///
+    /// Clips can be nested - sprites are then drawn only where all pushed rectangles overlap.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = match self.clip_stack.last() {
+            Some(current) => current.intersection(&rect),
+            None => rect,
+        };
+        self.clip_stack.push(clip);
+    }
+
+    /// Draws sprites in a `content` callback only where sprites drawn in a `mask` callback are
+    ///
+    /// Mask sprites are not visible themselves - they only define the masked area with pixels
+    /// which alpha (including tint) is at least 0.5. Masks can be nested by calling this
+    /// method from a `content` callback.
+    pub fn with_mask(
+        &mut self,
+        mask: impl FnOnce(&mut RenderCommands),
+        content: impl FnOnce(&mut RenderCommands),
+    ) {
+        let Stencil::Test(depth) = self.stencil else {
+            panic!("with_mask cannot be called while drawing a mask");
+        };
+        let inner_depth = depth.checked_add(1).expect("Too many nested masks");
+
+        let mask_start = self.blits.len();
+        self.stencil = Stencil::Write(depth);
+        mask(self);
+        let mask_end = self.blits.len();
+
+        self.stencil = Stencil::Test(inner_depth);
+        content(self);
+
+        let erase_blits: Vec<_> = self.blits[mask_start..mask_end]
+            .iter()
+            .map(|blit| BlitCommand {
+                stencil: Stencil::Erase(inner_depth),
+                ..blit.clone()
+            })
+            .collect();
+        self.blits.extend(erase_blits);
+        self.stencil = Stencil::Test(depth);
+    }
+
+    /// Removes clip rectangle added by the last [`RenderCommands::push_clip`] call
+    pub fn pop_clip(&mut self) {
+        self.clip_stack
+            .pop()
+            .expect("pop_clip called without matching push_clip");
+    }
+
+    /// Moves blits of other commands after blits of these ones, e.g. ones recorded on another thread
+    ///
+    /// Appended blits are restricted by current clip and mask, as if they were drawn here.
+    /// Clear color of other commands is ignored.
+    pub fn append(&mut self, other: RenderCommands) {
+        let Stencil::Test(depth) = self.stencil else {
+            panic!("append cannot be called while drawing a mask");
+        };
+        let start = self.blits.len();
+        self.blits.extend(other.blits);
+
+        let clip = self.clip_stack.last();
+        for blit in &mut self.blits[start..] {
+            blit.stencil = blit.stencil.nested_in(depth);
+            if let Some(clip) = clip {
+                blit.clip = Some(blit.clip.map_or(*clip, |own| own.intersection(clip)));
+            }
+        }
+    }
+
+    /// Returns color that target is cleared with, if any
+    pub fn clear_color(&self) -> Option<Color> {
+        self.clear_color
+    }
+
+    /// Returns drawn sprites in submission order, including ones defining masks
+    pub fn blits(&self) -> &[BlitCommand] {
+        &self.blits
+    }
 }
 
 impl Default for RenderCommands {
@@ -134,6 +639,8 @@ impl Default for RenderCommands {
         Self {
             clear_color: Some(Color::BLACK),
             blits: vec![],
+            clip_stack: vec![],
+            stencil: Stencil::Test(0),
         }
     }
 }
@@ -149,9 +656,38 @@ pub trait TextureData {
 
 #[cfg(test)]
 mod tests {
-    use crate::renderer::Color;
+    use cgmath::Vector2;
+    use image::Rgba;
 
-    use super::RenderCommands;
+    use std::thread;
+
+    use crate::renderer::{
+        config::RendererConfig,
+        rect::Rect,
+        sprite::Sprite,
+        testing::{scenes::Pixel, skip_without_adapter},
+        texture_ref::TextureRefManager,
+        Color, RenderThreadPanic, Renderer,
+    };
+
+    use super::{RenderCommands, Stencil};
+
+    fn sprite() -> Sprite {
+        Sprite {
+            texture: TextureRefManager::new().next(),
+            size: Vector2::new(8, 8),
+        }
+    }
+
+    #[test]
+    fn test_render_thread_panic_message() {
+        let panic = thread::spawn(|| panic!("lost device {}", 0))
+            .join()
+            .map_err(RenderThreadPanic)
+            .unwrap_err();
+        assert_eq!(Some("lost device 0"), panic.message());
+        assert_eq!("render thread panicked: lost device 0", panic.to_string());
+    }
 
     #[test]
     fn test_set_clear_color() {
@@ -159,4 +695,121 @@ mod tests {
         render_commands.set_clear_color(Color::RED);
         assert_eq!(Some(Color::RED), render_commands.clear_color)
     }
+
+    #[test]
+    fn test_nested_clips_intersect() {
+        let mut render_commands = RenderCommands::default();
+        render_commands.push_clip(Rect::new((0, 0), (10, 10)));
+        render_commands.push_clip(Rect::new((5, 5), (10, 10)));
+        render_commands.draw(&sprite());
+        render_commands.pop_clip();
+        render_commands.draw(&sprite());
+        render_commands.pop_clip();
+        render_commands.draw(&sprite());
+
+        let clips: Vec<_> = render_commands.blits.iter().map(|b| b.clip).collect();
+        assert_eq!(
+            vec![
+                Some(Rect::new((5, 5), (5, 5))),
+                Some(Rect::new((0, 0), (10, 10))),
+                None
+            ],
+            clips
+        );
+    }
+
+    #[test]
+    fn test_with_mask_writes_and_erases_stencil() {
+        let mut render_commands = RenderCommands::default();
+        render_commands.with_mask(
+            |mask| {
+                mask.draw(&sprite()).at((4, 4));
+            },
+            |content| {
+                content.draw(&sprite());
+            },
+        );
+        render_commands.draw(&sprite());
+
+        let stencils: Vec<_> = render_commands.blits.iter().map(|b| b.stencil).collect();
+        assert_eq!(
+            vec![
+                Stencil::Write(0),
+                Stencil::Test(1),
+                Stencil::Erase(1),
+                Stencil::Test(0)
+            ],
+            stencils
+        );
+        assert_eq!(Vector2::new(4, 4), render_commands.blits[2].position);
+    }
+
+    #[test]
+    fn test_append_nests_blits_in_current_clip_and_mask() {
+        let mut fragment = RenderCommands::default();
+        fragment.set_clear_color(Color::RED);
+        fragment.push_clip(Rect::new((2, 2), (10, 10)));
+        fragment.with_mask(
+            |mask| {
+                mask.draw(&sprite());
+            },
+            |content| {
+                content.draw(&sprite()).at((1, 1));
+            },
+        );
+
+        let mut render_commands = RenderCommands::default();
+        render_commands.draw(&sprite());
+        render_commands.push_clip(Rect::new((0, 0), (6, 6)));
+        render_commands.with_mask(
+            |mask| {
+                mask.draw(&sprite());
+            },
+            |content| content.append(fragment),
+        );
+
+        assert_eq!(Some(Color::BLACK), render_commands.clear_color);
+        let blits: Vec<_> = render_commands
+            .blits
+            .iter()
+            .map(|b| (b.stencil, b.clip))
+            .collect();
+        let clip = Some(Rect::new((0, 0), (6, 6)));
+        let nested_clip = Some(Rect::new((2, 2), (4, 4)));
+        assert_eq!(
+            vec![
+                (Stencil::Test(0), None),
+                (Stencil::Write(0), clip),
+                (Stencil::Write(1), nested_clip),
+                (Stencil::Test(2), nested_clip),
+                (Stencil::Erase(2), nested_clip),
+                (Stencil::Erase(1), clip),
+            ],
+            blits
+        );
+        assert_eq!(Vector2::new(1, 1), render_commands.blits[3].position);
+    }
+
+    #[test]
+    fn test_render_parallel_merges_callbacks_in_order() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 1), RendererConfig::default()))
+        else {
+            return;
+        };
+        let red = renderer.create_sprite(Pixel([255, 0, 0, 255]));
+        let green = renderer.create_sprite(Pixel([0, 255, 0, 255]));
+        let frame = renderer.capture_next_frame();
+        let callbacks = [(&red, Color::BLUE), (&green, Color::WHITE)].map(|(sprite, clear)| {
+            move |commands: &mut RenderCommands| {
+                commands.set_clear_color(clear);
+                commands.draw(sprite);
+            }
+        });
+        renderer.render_parallel(callbacks);
+
+        let frame = frame.recv().unwrap();
+        assert_eq!(Rgba([0, 255, 0, 255]), *frame.get_pixel(0, 0));
+        assert_eq!(Rgba([0, 0, 255, 255]), *frame.get_pixel(1, 0));
+    }
 }
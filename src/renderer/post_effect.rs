@@ -0,0 +1,175 @@
+use std::{borrow::Cow, error::Error, fmt};
+
+/// Maximal number of parameters that can be passed to a single post effect
+pub const MAX_POST_EFFECT_PARAMS: usize = 16;
+
+/// WGSL source prepended to every effect shader
+pub(crate) const PRELUDE: &str = include_str!("effects/prelude.wgsl");
+
+/// Describes a full-screen pass applied to a rendered frame before it is presented
+///
+/// Effect shaders are written in WGSL and are prepended with a prelude which provides:
+/// - `PostVertexOutput` struct with `uv` field, which should be taken by `fs_main` fragment shader,
+/// - `sample_input(uv)` function returning color of a frame rendered so far,
+/// - `param(index)` function returning parameter set by [`PostEffect::with_params`],
+/// - `post.resolution` with size of a frame in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostEffect {
+    pub(crate) shader: Cow<'static, str>,
+    pub(crate) params: [f32; MAX_POST_EFFECT_PARAMS],
+}
+
+/// Error returned when a custom effect shader is invalid, with a report pointing at the error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostEffectError {
+    /// Shader is not valid WGSL
+    Parse(String),
+    /// Shader parses, but fails validation, e.g. because of mismatched types
+    Validation(String),
+    /// Shader doesn't define `fs_main` fragment shader
+    MissingFragmentShader,
+}
+
+impl fmt::Display for PostEffectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostEffectError::Parse(report) => write!(f, "invalid WGSL: {}", report),
+            PostEffectError::Validation(report) => write!(f, "invalid shader: {}", report),
+            PostEffectError::MissingFragmentShader => {
+                write!(f, "shader doesn't define fs_main fragment shader")
+            }
+        }
+    }
+}
+
+impl Error for PostEffectError {}
+
+impl PostEffect {
+    /// Creates effect from user-supplied WGSL source defining `fs_main` fragment shader
+    ///
+    /// Shader is validated together with the prelude, so errors are reported here instead of
+    /// failing on a render thread.
+    pub fn custom(shader: impl Into<Cow<'static, str>>) -> Result<PostEffect, PostEffectError> {
+        let shader = shader.into();
+        validate(&shader)?;
+        Ok(PostEffect::new(shader))
+    }
+
+    /// Creates effect from a built-in shader, which is validated by tests instead
+    fn new(shader: impl Into<Cow<'static, str>>) -> PostEffect {
+        PostEffect {
+            shader: shader.into(),
+            params: [0.0; MAX_POST_EFFECT_PARAMS],
+        }
+    }
+
+    /// Darkens every other row of `spacing` pixels by `intensity` (0.0 - 1.0)
+    pub fn scanlines(intensity: f32, spacing: f32) -> PostEffect {
+        PostEffect::new(include_str!("effects/scanlines.wgsl")).with_params(&[intensity, spacing])
+    }
+
+    /// Darkens frame's corners by `strength` (0.0 - 1.0), starting at `radius` from the center
+    pub fn vignette(strength: f32, radius: f32) -> PostEffect {
+        PostEffect::new(include_str!("effects/vignette.wgsl")).with_params(&[strength, radius])
+    }
+
+    /// Adjusts frame's colors; `brightness` of 0.0 and `contrast` and `saturation` of 1.0 leave it unchanged
+    pub fn color_grading(brightness: f32, contrast: f32, saturation: f32) -> PostEffect {
+        PostEffect::new(include_str!("effects/color_grading.wgsl"))
+            .with_params(&[brightness, contrast, saturation])
+    }
+
+    /// Makes parts of a frame brighter than `threshold` glow with a given `intensity`
+    pub fn bloom(threshold: f32, intensity: f32) -> PostEffect {
+        PostEffect::new(include_str!("effects/bloom.wgsl"))
+            .with_params(&[threshold, intensity, 2.0])
+    }
+
+    /// Sets parameters available in shader through `param(index)`
+    ///
+    /// Panics if more than [`MAX_POST_EFFECT_PARAMS`] parameters are given.
+    pub fn with_params(mut self, params: &[f32]) -> Self {
+        assert!(
+            params.len() <= MAX_POST_EFFECT_PARAMS,
+            "Post effect can take at most {} params",
+            MAX_POST_EFFECT_PARAMS
+        );
+        self.params = [0.0; MAX_POST_EFFECT_PARAMS];
+        self.params[..params.len()].copy_from_slice(params);
+        self
+    }
+}
+
+fn validate(shader: &str) -> Result<(), PostEffectError> {
+    let source = format!("{}\n{}", PRELUDE, shader);
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|error| PostEffectError::Parse(error.emit_to_string(&source)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|error| PostEffectError::Validation(error.emit_to_string(&source)))?;
+    let has_fragment_shader = module
+        .entry_points
+        .iter()
+        .any(|entry| entry.name == "fs_main" && entry.stage == naga::ShaderStage::Fragment);
+    if has_fragment_shader {
+        Ok(())
+    } else {
+        Err(PostEffectError::MissingFragmentShader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, PostEffect, PostEffectError, MAX_POST_EFFECT_PARAMS};
+
+    const INVERT: &str = "
+        @fragment
+        fn fs_main(in: PostVertexOutput) -> @location(0) vec4<f32> {
+            let color = sample_input(in.uv);
+            return vec4<f32>(1.0 - color.rgb, color.a);
+        }
+    ";
+
+    #[test]
+    fn test_with_params() {
+        let effect = PostEffect::custom(INVERT).unwrap().with_params(&[1.0, 2.0]);
+        assert_eq!([1.0, 2.0, 0.0], effect.params[..3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_with_too_many_params() {
+        PostEffect::scanlines(0.5, 1.0).with_params(&[0.0; MAX_POST_EFFECT_PARAMS + 1]);
+    }
+
+    #[test]
+    fn test_built_in_effects_are_valid() {
+        for effect in [
+            PostEffect::scanlines(0.5, 1.0),
+            PostEffect::vignette(0.5, 0.5),
+            PostEffect::color_grading(0.0, 1.0, 1.0),
+            PostEffect::bloom(0.8, 1.0),
+        ] {
+            assert_eq!(Ok(()), validate(&effect.shader));
+        }
+    }
+
+    #[test]
+    fn test_invalid_custom_effect() {
+        assert!(matches!(
+            PostEffect::custom("fn fs_main( {"),
+            Err(PostEffectError::Parse(_))
+        ));
+        assert!(matches!(
+            PostEffect::custom(INVERT.replace("1.0 - color.rgb", "1 - color.rgb")),
+            Err(PostEffectError::Validation(_))
+        ));
+        assert_eq!(
+            Err(PostEffectError::MissingFragmentShader),
+            PostEffect::custom("")
+        );
+    }
+}
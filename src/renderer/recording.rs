@@ -0,0 +1,258 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    iter,
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    error::{EncodingError, ImageFormatHint},
+    Delay, ImageError, ImageFormat, ImageResult, RgbaImage,
+};
+
+/// Trades GIF palette quality for encoding speed, in range `1..=30`
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+/// Formats frame sequences can be recorded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordingFormat {
+    /// Animated GIF, with a palette of up to 256 colors quantized for every frame
+    Gif,
+    /// Animated PNG, lossless but much bigger than GIF
+    Apng,
+}
+
+impl RecordingFormat {
+    fn from_path(path: &Path) -> io::Result<RecordingFormat> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gif") => Ok(RecordingFormat::Gif),
+            Some("png" | "apng") => Ok(RecordingFormat::Apng),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "recordings can be saved only as .gif, .png or .apng",
+            )),
+        }
+    }
+}
+
+/// Passes rendered frames to an encoder thread at a fixed rate
+#[derive(Debug)]
+pub(crate) struct FrameSampler {
+    tx: mpsc::Sender<RgbaImage>,
+    interval: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl FrameSampler {
+    /// Returns whether frame rendered at a given time should be recorded
+    pub(crate) fn wants_frame(&self, now: Instant) -> bool {
+        self.next_frame.is_none_or(|next_frame| now >= next_frame)
+    }
+
+    pub(crate) fn record(&mut self, frame: RgbaImage, now: Instant) {
+        // Frames rendered too slowly are recorded late, instead of in a burst catching up
+        let next_frame = self.next_frame.unwrap_or(now) + self.interval;
+        self.next_frame = Some(next_frame.max(now));
+        // Encoder thread stops only on errors, which are reported once recording stops
+        let _ = self.tx.send(frame);
+    }
+}
+
+/// Recording which is being encoded on a background thread
+pub(crate) struct Recording {
+    encoder: thread::JoinHandle<ImageResult<()>>,
+}
+
+impl Recording {
+    /// Creates a file and spawns a thread encoding frames passed by a returned sampler to it
+    pub(crate) fn start(path: &Path, fps: u32) -> io::Result<(Recording, FrameSampler)> {
+        let fps = fps.clamp(1, u16::MAX as u32);
+        let format = RecordingFormat::from_path(path)?;
+        // APNG's frame count is patched once recording stops, so file is read as well
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let file = BufWriter::new(file);
+        let (tx, rx) = mpsc::channel();
+        let encoder = thread::spawn(move || match format {
+            RecordingFormat::Gif => encode_gif(file, fps, rx),
+            RecordingFormat::Apng => encode_apng(file, fps, rx),
+        });
+        let sampler = FrameSampler {
+            tx,
+            interval: Duration::from_secs(1) / fps,
+            next_frame: None,
+        };
+        Ok((Recording { encoder }, sampler))
+    }
+
+    /// Waits until all recorded frames are encoded
+    pub(crate) fn finish(self) -> ImageResult<()> {
+        self.encoder.join().unwrap_or_else(|_| {
+            Err(ImageError::IoError(io::Error::other(
+                "recording encoder panicked",
+            )))
+        })
+    }
+}
+
+/// Returns frames which have the same size as the first one, as animations have fixed size
+fn same_sized_frames(rx: mpsc::Receiver<RgbaImage>) -> impl Iterator<Item = RgbaImage> {
+    let mut size = None;
+    rx.into_iter()
+        .filter(move |frame| *size.get_or_insert(frame.dimensions()) == frame.dimensions())
+}
+
+fn encode_gif(file: BufWriter<File>, fps: u32, rx: mpsc::Receiver<RgbaImage>) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new_with_speed(file, GIF_QUANTIZATION_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    for frame in same_sized_frames(rx) {
+        encoder.encode_frame(image::Frame::from_parts(frame, 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+fn encode_apng(
+    mut file: BufWriter<File>,
+    fps: u32,
+    rx: mpsc::Receiver<RgbaImage>,
+) -> ImageResult<()> {
+    let mut frames = same_sized_frames(rx);
+    let Some(first_frame) = frames.next() else {
+        return Ok(());
+    };
+    let (width, height) = first_frame.dimensions();
+    let png_error = |error| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Png),
+            error,
+        ))
+    };
+
+    let mut encoder = png::Encoder::new(&mut file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    // Number of frames is written before them, so it's patched once all are encoded
+    encoder.set_animated(u32::MAX, 0).map_err(png_error)?;
+    encoder.set_frame_delay(1, fps as u16).map_err(png_error)?;
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    let mut frame_count = 0;
+    for frame in iter::once(first_frame).chain(frames) {
+        writer.write_image_data(frame.as_raw()).map_err(png_error)?;
+        frame_count += 1;
+    }
+    writer.finish().map_err(png_error)?;
+
+    let file = file.into_inner().map_err(io::IntoInnerError::into_error)?;
+    patch_frame_count(file, frame_count)?;
+    Ok(())
+}
+
+/// Overwrites number of frames in an `acTL` chunk of an already written APNG file
+fn patch_frame_count(mut file: File, frame_count: u32) -> io::Result<()> {
+    // Animation control chunk comes right after few small header chunks
+    let mut header = vec![];
+    file.seek(SeekFrom::Start(0))?;
+    (&mut file).take(256).read_to_end(&mut header)?;
+    let chunk_type = header
+        .windows(4)
+        .position(|bytes| bytes == b"acTL")
+        .filter(|&position| position + 12 <= header.len())
+        .ok_or_else(|| io::Error::other("APNG file has no animation control chunk"))?;
+
+    // Chunk type is followed by frame and play counts, then by CRC of all three
+    let chunk = &mut header[chunk_type..chunk_type + 12];
+    chunk[4..8].copy_from_slice(&frame_count.to_be_bytes());
+    let crc = crc32fast::hash(chunk);
+    file.seek(SeekFrom::Start(chunk_type as u64 + 4))?;
+    file.write_all(&frame_count.to_be_bytes())?;
+    file.seek(SeekFrom::Start(chunk_type as u64 + 12))?;
+    file.write_all(&crc.to_be_bytes())?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{FrameSampler, Recording, RecordingFormat};
+
+    #[test]
+    fn test_format_from_extension() {
+        let format = |path| RecordingFormat::from_path(Path::new(path)).ok();
+        assert_eq!(Some(RecordingFormat::Gif), format("trailer.GIF"));
+        assert_eq!(Some(RecordingFormat::Apng), format("bug/report.apng"));
+        assert_eq!(Some(RecordingFormat::Apng), format("report.png"));
+        assert_eq!(None, format("trailer.mp4"));
+        assert_eq!(None, format("trailer"));
+    }
+
+    #[test]
+    fn test_sampler_keeps_frame_rate() {
+        let (tx, rx) = mpsc::channel();
+        let mut sampler = FrameSampler {
+            tx,
+            interval: Duration::from_millis(100),
+            next_frame: None,
+        };
+        let start = Instant::now();
+        let mut recorded = 0;
+        for frame in 0..20 {
+            let now = start + Duration::from_millis(frame * 25);
+            if sampler.wants_frame(now) {
+                sampler.record(Default::default(), now);
+                recorded += 1;
+            }
+        }
+        assert_eq!(5, recorded);
+        assert_eq!(5, rx.try_iter().count());
+    }
+
+    #[test]
+    fn test_recording_to_apng() {
+        let path =
+            std::env::temp_dir().join(format!("floppa2_test_recording_{}.png", std::process::id()));
+        let (recording, mut sampler) = Recording::start(&path, 10).unwrap();
+        let start = Instant::now();
+        for (frame, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+            .into_iter()
+            .enumerate()
+        {
+            let now = start + Duration::from_millis(frame as u64 * 100);
+            sampler.record(RgbaImage::from_pixel(2, 2, Rgba(color)), now);
+        }
+        drop(sampler);
+        recording.finish().unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let frames = reader
+            .info()
+            .animation_control()
+            .map(|actl| actl.num_frames);
+        let mut last_frame = vec![0; reader.output_buffer_size()];
+        while reader.next_frame(&mut last_frame).is_ok() {}
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Some(3), frames);
+        assert_eq!([0, 0, 255, 255], last_frame[..4]);
+    }
+}
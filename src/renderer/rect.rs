@@ -0,0 +1,73 @@
+use cgmath::Vector2;
+
+/// An axis-aligned rectangle in screen pixels, with position of its bottom-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub position: Vector2<u32>,
+    pub size: Vector2<u32>,
+}
+
+impl Rect {
+    /// Creates rectangle at given position with given size
+    pub fn new(position: impl Into<Vector2<u32>>, size: impl Into<Vector2<u32>>) -> Rect {
+        Rect {
+            position: position.into(),
+            size: size.into(),
+        }
+    }
+
+    /// Returns whether rectangle covers no pixels
+    pub fn is_empty(&self) -> bool {
+        self.size.x == 0 || self.size.y == 0
+    }
+
+    /// Returns part of a plane covered by both rectangles
+    ///
+    /// If rectangles do not overlap, an empty rectangle is returned.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let left = self.position.x.max(other.position.x);
+        let bottom = self.position.y.max(other.position.y);
+        let right = self
+            .position
+            .x
+            .saturating_add(self.size.x)
+            .min(other.position.x.saturating_add(other.size.x));
+        let top = self
+            .position
+            .y
+            .saturating_add(self.size.y)
+            .min(other.position.y.saturating_add(other.size.y));
+        Rect::new(
+            (left, bottom),
+            (right.saturating_sub(left), top.saturating_sub(bottom)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rect;
+
+    #[test]
+    fn test_intersection_overlapping() {
+        let a = Rect::new((0, 0), (10, 10));
+        let b = Rect::new((5, 2), (10, 4));
+        assert_eq!(Rect::new((5, 2), (5, 4)), a.intersection(&b));
+        assert_eq!(a.intersection(&b), b.intersection(&a));
+    }
+
+    #[test]
+    fn test_intersection_disjoint() {
+        let a = Rect::new((0, 0), (10, 10));
+        let b = Rect::new((20, 20), (5, 5));
+        assert!(a.intersection(&b).is_empty());
+    }
+
+    #[test]
+    fn test_intersection_near_numeric_limit() {
+        let a = Rect::new((u32::MAX - 2, 0), (10, 10));
+        let b = Rect::new((0, 0), (u32::MAX, 4));
+        assert_eq!(Rect::new((u32::MAX - 2, 0), (2, 4)), a.intersection(&b));
+    }
+}
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use crate::renderer::{rect::Rect, texture_ref::TextureRef, BlitCommand, Stencil};
+
+use super::{buffers::instances::Instance, texture_array::TextureArray};
+
+/// Consecutive blits that can be drawn with a single draw call
+#[derive(Debug, PartialEq)]
+pub(crate) struct Batch {
+    /// Texture sprites are sampled from - `None` if they are sampled from a texture array
+    pub(crate) texture: Option<TextureRef>,
+    pub(crate) clip: Option<Rect>,
+    pub(crate) stencil: Stencil,
+    pub(crate) instances: Range<u32>,
+}
+
+/// Converts blits to instances, grouping them into batches while preserving submission order
+///
+/// If texture array is given, sprites stored in it are batched together regardless of their textures.
+pub(crate) fn build_batches(
+    blits: &[BlitCommand],
+    array: Option<&TextureArray>,
+) -> (Vec<Instance>, Vec<Batch>) {
+    let mut instances = Vec::with_capacity(blits.len());
+    let mut batches: Vec<Batch> = vec![];
+    for blit in blits {
+        let index = instances.len() as u32;
+        let instance = Instance::from_blit(blit);
+        let (instance, texture) = match array.and_then(|array| array.layer(&blit.texture_id)) {
+            Some(layer) => (instance.in_array_layer(layer), None),
+            None => (instance, Some(blit.texture_id)),
+        };
+        instances.push(instance);
+        match batches.last_mut() {
+            Some(batch)
+                if batch.texture == texture
+                    && batch.clip == blit.clip
+                    && batch.stencil == blit.stencil =>
+            {
+                batch.instances.end = index + 1;
+            }
+            _ => batches.push(Batch {
+                texture,
+                clip: blit.clip,
+                stencil: blit.stencil,
+                instances: index..index + 1,
+            }),
+        }
+    }
+    (instances, batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{
+        config::RendererConfig,
+        rect::Rect,
+        render_thread::{gpu::Gpu, textures::Textures},
+        sprite::Sprite,
+        testing::skip_without_adapter,
+        texture_ref::TextureRefManager,
+        RenderCommands, Stencil, TEXTURE_ARRAY_LAYER_SIZE,
+    };
+
+    use crate::renderer::layers::sort_by_layer;
+
+    use super::{build_batches, Batch, Instance};
+
+    #[test]
+    fn test_batches_preserve_order_across_clips() {
+        let textures = TextureRefManager::new();
+        let a = Sprite {
+            texture: textures.next(),
+            size: (8, 8).into(),
+        };
+        let b = Sprite {
+            texture: textures.next(),
+            size: (8, 8).into(),
+        };
+        let clip = Rect::new((0, 0), (4, 4));
+
+        let mut commands = RenderCommands::default();
+        commands.draw(&a);
+        commands.draw(&a);
+        commands.push_clip(clip);
+        commands.draw(&a);
+        commands.pop_clip();
+        commands.draw(&b);
+        commands.draw(&a);
+
+        let (instances, batches) = build_batches(&commands.blits, None);
+        assert_eq!(5, instances.len());
+        assert_eq!(
+            vec![
+                Batch {
+                    texture: Some(a.texture),
+                    clip: None,
+                    stencil: Stencil::Test(0),
+                    instances: 0..2
+                },
+                Batch {
+                    texture: Some(a.texture),
+                    clip: Some(clip),
+                    stencil: Stencil::Test(0),
+                    instances: 2..3
+                },
+                Batch {
+                    texture: Some(b.texture),
+                    clip: None,
+                    stencil: Stencil::Test(0),
+                    instances: 3..4
+                },
+                Batch {
+                    texture: Some(a.texture),
+                    clip: None,
+                    stencil: Stencil::Test(0),
+                    instances: 4..5
+                },
+            ],
+            batches
+        );
+    }
+
+    #[test]
+    fn test_batches_follow_layers() {
+        let textures = TextureRefManager::new();
+        let a = Sprite {
+            texture: textures.next(),
+            size: (8, 8).into(),
+        };
+        let b = Sprite {
+            texture: textures.next(),
+            size: (8, 8).into(),
+        };
+
+        let mut commands = RenderCommands::default();
+        commands.draw(&b).at((0, 0)).on_layer(1);
+        commands.draw(&a).at((1, 0));
+        commands.draw(&b).at((2, 0)).on_layer(1);
+        commands.draw(&a).at((3, 0));
+
+        let submitted = commands.blits.clone();
+        let blits = sort_by_layer(commands.blits);
+        let (instances, batches) = build_batches(&blits, None);
+        assert_eq!(
+            vec![(Some(a.texture), 0..2), (Some(b.texture), 2..4)],
+            batches
+                .into_iter()
+                .map(|batch| (batch.texture, batch.instances))
+                .collect::<Vec<_>>()
+        );
+        let expected: Vec<_> = [1, 3, 0, 2]
+            .into_iter()
+            .map(|i| Instance::from_blit(&submitted[i]))
+            .collect();
+        assert_eq!(expected, instances);
+    }
+
+    #[test]
+    fn test_array_batches_interleaved_sprites_except_too_big_ones() {
+        let Some(gpu) = skip_without_adapter(Gpu::headless((4, 4), &RendererConfig::default()))
+        else {
+            return;
+        };
+        let mut textures = Textures::new(&gpu);
+        textures.set_array_enabled(&gpu, true);
+        let manager = TextureRefManager::new();
+        let mut load = |size: (u32, u32)| {
+            let texture = manager.next();
+            let data = vec![255; (4 * size.0 * size.1) as usize];
+            textures.load_texture(&gpu, texture, &data, size.into());
+            Sprite {
+                texture,
+                size: size.into(),
+            }
+        };
+        let a = load((8, 8));
+        let b = load((4, 2));
+        let big = load((TEXTURE_ARRAY_LAYER_SIZE + 1, 1));
+
+        let mut commands = RenderCommands::default();
+        commands.draw(&a);
+        commands.draw(&b);
+        commands.draw(&a);
+        commands.draw(&big);
+        commands.draw(&b);
+
+        let array = textures.array().unwrap();
+        let (instances, batches) = build_batches(&commands.blits, Some(array));
+        assert_eq!(
+            vec![(None, 0..3), (Some(big.texture), 3..4), (None, 4..5)],
+            batches
+                .into_iter()
+                .map(|batch| (batch.texture, batch.instances))
+                .collect::<Vec<_>>()
+        );
+        let layer_b = array.layer(&b.texture).unwrap();
+        assert_eq!(
+            Instance::from_blit(&commands.blits[1]).in_array_layer(layer_b),
+            instances[1]
+        );
+        assert_eq!([4.0 / 256.0, 2.0 / 256.0], layer_b.uv_scale);
+        assert_eq!(Instance::from_blit(&commands.blits[3]), instances[3]);
+    }
+}
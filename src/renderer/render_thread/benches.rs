@@ -0,0 +1,124 @@
+//! Compares recording a frame into a single render pass with recording a render pass per batch.
+//!
+//! Run with `cargo test --release -- --ignored --nocapture bench_`.
+
+use std::time::{Duration, Instant};
+
+use crate::renderer::{
+    config::RendererConfig, layers::sort_by_layer, sprite::Sprite, texture_ref::TextureRefManager,
+    RenderCommands, RenderError,
+};
+
+use super::{
+    batches::build_batches,
+    pipeline::{PipelineBuffers, RenderPass},
+    RendererThread,
+};
+
+const SIZE: (u32, u32) = (800, 600);
+const TEXTURE_COUNT: usize = 256;
+const BLIT_COUNT: usize = 10_000;
+const FRAMES: u32 = 100;
+
+fn load_sprites(thread: &mut RendererThread) -> Vec<Sprite> {
+    let texture_refs = TextureRefManager::new();
+    (0..TEXTURE_COUNT)
+        .map(|i| {
+            let texture = texture_refs.next();
+            let data = vec![i as u8; 4 * 4 * 4];
+            thread
+                .textures
+                .load_texture(&thread.gpu, texture, &data, (4, 4).into());
+            Sprite {
+                texture,
+                size: (4, 4).into(),
+            }
+        })
+        .collect()
+}
+
+fn commands(sprites: &[Sprite]) -> RenderCommands {
+    let mut commands = RenderCommands::default();
+    for i in 0..BLIT_COUNT {
+        let position = ((i as u32 * 4) % SIZE.0, (i as u32 * 4 / SIZE.0) * 4);
+        commands.draw(&sprites[i % sprites.len()]).at(position);
+    }
+    commands
+}
+
+fn render_pass_per_batch(
+    thread: &mut RendererThread,
+    command: RenderCommands,
+) -> Result<bool, RenderError> {
+    let Some(frame) = thread.gpu.acquire_frame()? else {
+        return Ok(false);
+    };
+    let blits = sort_by_layer(command.blits);
+    let (data, batches) = build_batches(&blits, thread.textures.array());
+    thread.instances.write_instances(&thread.gpu, &data);
+
+    let command_buffers: Vec<_> = batches
+        .iter()
+        .enumerate()
+        .map(|(i, batch)| {
+            let mut encoder = thread
+                .gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            thread.pipeline.encode_pass(
+                &mut encoder,
+                RenderPass {
+                    buffers: PipelineBuffers {
+                        uniform: &thread.uniform,
+                        textures: &thread.textures,
+                        instances: &thread.instances,
+                    },
+                    view: frame.view(),
+                    stencil_view: thread.stencil.as_ref().unwrap().view(),
+                    clear_color: command.clear_color.filter(|_| i == 0).map(Into::into),
+                    clear_stencil: i == 0,
+                    target_size: thread.size,
+                    batches: std::slice::from_ref(batch),
+                },
+            );
+            encoder.finish()
+        })
+        .collect();
+    thread.gpu.queue().submit(command_buffers);
+    frame.present();
+    Ok(true)
+}
+
+fn measure<T>(
+    thread: &mut RendererThread,
+    sprites: &[Sprite],
+    render: impl Fn(&mut RendererThread, RenderCommands) -> Result<T, RenderError>,
+) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        render(thread, commands(sprites)).unwrap();
+        thread.gpu.device().poll(wgpu::Maintain::Wait);
+    }
+    start.elapsed() / FRAMES
+}
+
+#[test]
+#[ignore]
+fn bench_single_pass_vs_pass_per_batch() {
+    let Some(mut thread) = RendererThread::headless(SIZE, &RendererConfig::default()) else {
+        eprintln!("No adapter available, skipping benchmark");
+        return;
+    };
+    let sprites = load_sprites(&mut thread);
+
+    // Warm up both paths before measuring
+    measure(&mut thread, &sprites, RendererThread::render);
+    measure(&mut thread, &sprites, render_pass_per_batch);
+
+    let single_pass = measure(&mut thread, &sprites, RendererThread::render);
+    let pass_per_batch = measure(&mut thread, &sprites, render_pass_per_batch);
+    println!(
+        "{} blits, {} textures: single pass {:?}/frame, pass per batch {:?}/frame",
+        BLIT_COUNT, TEXTURE_COUNT, single_pass, pass_per_batch
+    );
+}
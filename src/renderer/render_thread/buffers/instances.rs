@@ -1,31 +1,37 @@
 use cgmath::Matrix4;
 use wgpu::{BufferUsages, VertexAttribute};
 
-use crate::renderer::{render_thread::gpu::Gpu, BlitCommand};
+use crate::renderer::{
+    render_thread::{gpu::Gpu, texture_array::ArrayLayer},
+    BlitCommand,
+};
 
 use std::mem;
 
+/// Initial number of instances buffer can hold, before it has to grow
 const INSTANCE_BUFFER_CAPACITY: u64 = 1 << 12;
 
 pub(crate) struct InstanceBuffer {
     buffer: wgpu::Buffer,
+    capacity: u64,
 }
 
 impl InstanceBuffer {
     pub(crate) fn new(gpu: &Gpu) -> InstanceBuffer {
-        let buffer_descriptor = wgpu::BufferDescriptor {
-            label: None,
-            size: INSTANCE_BUFFER_CAPACITY * (mem::size_of::<Instance>() as u64),
-            usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
-            mapped_at_creation: false,
-        };
-        let buffer = gpu.device().create_buffer(&buffer_descriptor);
-        InstanceBuffer { buffer }
+        InstanceBuffer {
+            buffer: Self::create_buffer(gpu, INSTANCE_BUFFER_CAPACITY),
+            capacity: INSTANCE_BUFFER_CAPACITY,
+        }
     }
 
-    pub(crate) fn write_instances(&self, gpu: &Gpu, instances: &[Instance]) {
-        gpu
-            .queue()
+    /// Uploads instances of a frame, growing the buffer to the next power of two if they don't fit
+    pub(crate) fn write_instances(&mut self, gpu: &Gpu, instances: &[Instance]) {
+        let count = instances.len() as u64;
+        if count > self.capacity {
+            self.capacity = count.next_power_of_two();
+            self.buffer = Self::create_buffer(gpu, self.capacity);
+        }
+        gpu.queue()
             .write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
     }
 
@@ -33,6 +39,15 @@ impl InstanceBuffer {
         &self.buffer
     }
 
+    fn create_buffer(gpu: &Gpu, capacity: u64) -> wgpu::Buffer {
+        gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: capacity * (mem::size_of::<Instance>() as u64),
+            usage: BufferUsages::COPY_DST | BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        })
+    }
+
     pub(crate) fn layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
@@ -43,14 +58,15 @@ impl InstanceBuffer {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct Instance {
     model: [[f32; 4]; 4],
     color: [f32; 4],
+    texture_region: [f32; 4],
 }
 
 impl Instance {
-    const ATTR_ARRAY: [VertexAttribute; 5] = wgpu::vertex_attr_array![10 => Float32x4, 11 => Float32x4, 12 => Float32x4, 13 => Float32x4, 14 => Float32x4];
+    const ATTR_ARRAY: [VertexAttribute; 6] = wgpu::vertex_attr_array![10 => Float32x4, 11 => Float32x4, 12 => Float32x4, 13 => Float32x4, 14 => Float32x4, 15 => Float32x4];
 
     pub(crate) fn from_blit(blit: &BlitCommand) -> Instance {
         let translation_matrix =
@@ -60,12 +76,21 @@ impl Instance {
         let instance_matrix = translation_matrix * scale_matrix;
         Instance {
             model: instance_matrix.into(),
-            color: [
-                blit.color.r as f32,
-                blit.color.g as f32,
-                blit.color.b as f32,
-                blit.color.a as f32,
+            color: [blit.color.r, blit.color.g, blit.color.b, blit.color.a],
+            texture_region: [1.0, 1.0, 0.0, 0.0],
+        }
+    }
+
+    /// Makes instance sample its sprite from a given texture array layer
+    pub(crate) fn in_array_layer(self, layer: ArrayLayer) -> Instance {
+        Instance {
+            texture_region: [
+                layer.uv_scale[0],
+                layer.uv_scale[1],
+                layer.index as f32,
+                0.0,
             ],
+            ..self
         }
     }
 }
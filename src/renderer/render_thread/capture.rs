@@ -0,0 +1,127 @@
+use std::sync::mpsc;
+
+use cgmath::Vector2;
+use image::RgbaImage;
+
+use super::gpu::Gpu;
+
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Returns whether frames of a given format can be converted to RGBA images
+pub(crate) fn is_supported_format(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb
+            | wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// Copy of a frame in a buffer which can be read once GPU finishes the frame
+pub(crate) struct FrameCapture {
+    buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+    size: Vector2<u32>,
+    bgra: bool,
+}
+
+impl FrameCapture {
+    /// Records copying a frame's texture to a mappable buffer
+    pub(crate) fn encode(
+        gpu: &Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        size: Vector2<u32>,
+    ) -> FrameCapture {
+        // Rows of copied textures have to be aligned, so they are padded in the buffer
+        let padded_bytes_per_row =
+            (size.x * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = gpu.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame capture"),
+            size: padded_bytes_per_row as u64 * size.y as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        FrameCapture {
+            buffer,
+            padded_bytes_per_row,
+            size,
+            bgra: matches!(
+                texture.format(),
+                wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+            ),
+        }
+    }
+
+    /// Waits for GPU to finish the frame and converts it to an image
+    pub(crate) fn read(self, gpu: &Gpu) -> Option<RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        gpu.device().poll(wgpu::Maintain::Wait);
+        rx.recv().ok()?.ok()?;
+
+        let padded = slice.get_mapped_range();
+        let image = unpad_rows(&padded, self.padded_bytes_per_row, self.size, self.bgra);
+        drop(padded);
+        self.buffer.unmap();
+        RgbaImage::from_raw(self.size.x, self.size.y, image)
+    }
+}
+
+/// Removes row padding, swizzling BGRA pixels to RGBA if needed
+fn unpad_rows(padded: &[u8], padded_bytes_per_row: u32, size: Vector2<u32>, bgra: bool) -> Vec<u8> {
+    let bytes_per_row = (size.x * BYTES_PER_PIXEL) as usize;
+    let mut pixels: Vec<u8> = padded
+        .chunks(padded_bytes_per_row as usize)
+        .take(size.y as usize)
+        .flat_map(|row| &row[..bytes_per_row])
+        .copied()
+        .collect();
+    if bgra {
+        for pixel in pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize) {
+            pixel.swap(0, 2);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unpad_rows;
+
+    #[test]
+    fn test_unpad_rows_swizzles_bgra() {
+        let padded = [
+            1, 2, 3, 4, 0, 0, 0, 0, //
+            5, 6, 7, 8, 0, 0, 0, 0,
+        ];
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+            unpad_rows(&padded, 8, (1, 2).into(), false)
+        );
+        assert_eq!(
+            vec![3, 2, 1, 4, 7, 6, 5, 8],
+            unpad_rows(&padded, 8, (1, 2).into(), true)
+        );
+    }
+}
@@ -1,52 +1,196 @@
 use cgmath::Vector2;
 
-use crate::renderer::CompatibleWindow;
+use crate::renderer::{config::RendererConfig, Color, CompatibleWindow, RenderError};
+
+use super::capture;
+
+#[cfg(any(test, feature = "testing"))]
+const OFFSCREEN_USAGE: wgpu::TextureUsages =
+    wgpu::TextureUsages::RENDER_ATTACHMENT.union(wgpu::TextureUsages::COPY_SRC);
+
+/// Where rendered frames end up
+enum Output {
+    Surface(wgpu::Surface),
+    /// Texture is created once output has non-zero size
+    #[cfg(any(test, feature = "testing"))]
+    Offscreen(Option<wgpu::Texture>),
+}
 
 pub(crate) struct Gpu {
     device: wgpu::Device,
     queue: wgpu::Queue,
-    surface: wgpu::Surface,
+    output: Output,
     surface_format: wgpu::TextureFormat,
     alpha_mode: wgpu::CompositeAlphaMode,
+    present_mode: wgpu::PresentMode,
+    output_usage: wgpu::TextureUsages,
+    size: Vector2<u32>,
+}
+
+/// A texture that single frame is rendered to
+pub(crate) struct Frame {
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    view: wgpu::TextureView,
+}
+
+impl Frame {
+    pub(crate) fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub(crate) fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
 }
 
 impl Gpu {
     pub(crate) fn compatible_with(
         window: impl CompatibleWindow,
         size: impl Into<Vector2<u32>>,
+        config: &RendererConfig,
     ) -> Gpu {
-        let instance = wgpu::Instance::default();
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
-        let adapter = pollster::block_on(Self::get_adapter(&instance, &surface));
-        let (device, queue) = pollster::block_on(Self::get_compatible_device_queue(&adapter));
+        let connect = |config: &RendererConfig| {
+            let instance = Self::create_instance(config.wgpu_backends());
+            let surface = unsafe { instance.create_surface(&window) }.unwrap();
+            let adapter = pollster::block_on(Self::get_adapter(&instance, Some(&surface), config));
+            adapter.map(|adapter| (surface, adapter))
+        };
+        // Requested backends or fallback adapter may be unavailable, so any adapter is accepted then
+        let (surface, adapter) = connect(config)
+            .or_else(|| {
+                connect(&RendererConfig::default().power_preference(config.power_preference))
+            })
+            .expect("No compatible adapter found");
+        let (device, queue) =
+            pollster::block_on(Self::get_compatible_device_queue(&adapter, config));
 
         let capabilities = surface.get_capabilities(&adapter);
-        let surface_format = capabilities.formats[0];
-        let alpha_mode = capabilities.alpha_modes[0];
+        let surface_format = config.choose_surface_format(&capabilities.formats);
+        let alpha_mode = config.choose_alpha_mode(&capabilities.alpha_modes);
+        let present_mode = config.choose_present_mode(&capabilities.present_modes);
+        // Copying frames allows capturing screenshots, but not every surface supports it
+        let output_usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (capabilities.usages & wgpu::TextureUsages::COPY_SRC);
 
-        let gpu = Gpu {
+        let mut gpu = Gpu {
             device,
             queue,
-            surface,
+            output: Output::Surface(surface),
             surface_format,
             alpha_mode,
+            present_mode,
+            output_usage,
+            size: size.into(),
         };
-        gpu.resize(size);
+        gpu.resize(gpu.size);
         gpu
     }
 
-    pub(crate) fn resize(&self, size: impl Into<Vector2<u32>>) {
-        let size = size.into();
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: self.surface_format,
-            alpha_mode: self.alpha_mode,
-            width: size.x,
-            height: size.y,
+    /// Creates GPU rendering to an offscreen texture, if any adapter is available
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn headless(size: impl Into<Vector2<u32>>, config: &RendererConfig) -> Option<Gpu> {
+        let connect = |config: &RendererConfig| {
+            let instance = Self::create_instance(config.wgpu_backends());
+            pollster::block_on(Self::get_adapter(&instance, None, config))
+        };
+        let adapter = connect(config).or_else(|| {
+            connect(&RendererConfig::default().power_preference(config.power_preference))
+        })?;
+        let (device, queue) =
+            pollster::block_on(Self::get_compatible_device_queue(&adapter, config));
+        let mut gpu = Gpu {
+            device,
+            queue,
+            output: Output::Offscreen(None),
+            surface_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
             present_mode: wgpu::PresentMode::Fifo,
-            view_formats: vec![],
+            output_usage: OFFSCREEN_USAGE,
+            size: size.into(),
         };
-        self.surface.configure(&self.device, &config);
+        gpu.resize(gpu.size);
+        Some(gpu)
+    }
+
+    /// Reconfigures output for a new size, which is deferred while a window is minimized
+    pub(crate) fn resize(&mut self, size: impl Into<Vector2<u32>>) {
+        self.size = size.into();
+        if self.is_minimized() {
+            return;
+        }
+        match &self.output {
+            Output::Surface(surface) => self.configure_surface(surface),
+            #[cfg(any(test, feature = "testing"))]
+            Output::Offscreen(_) => {
+                let texture =
+                    Self::create_offscreen_texture(&self.device, self.surface_format, self.size);
+                self.output = Output::Offscreen(Some(texture));
+            }
+        }
+    }
+
+    /// Returns whether output has zero area, so nothing can be rendered to it
+    pub(crate) fn is_minimized(&self) -> bool {
+        self.size.x == 0 || self.size.y == 0
+    }
+
+    /// Returns texture next frame should be rendered to, or `None` if the frame should be skipped
+    pub(crate) fn acquire_frame(&self) -> Result<Option<Frame>, RenderError> {
+        if self.is_minimized() {
+            return Ok(None);
+        }
+        match &self.output {
+            Output::Surface(surface) => {
+                let surface_texture = match surface.get_current_texture() {
+                    // Surface no longer matches a window, e.g. after resizing or restoring it
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        self.configure_surface(surface);
+                        surface.get_current_texture()
+                    }
+                    result => result,
+                };
+                let surface_texture = match surface_texture {
+                    Ok(surface_texture) => surface_texture,
+                    Err(wgpu::SurfaceError::OutOfMemory) => return Err(RenderError::OutOfMemory),
+                    Err(_) => return Ok(None),
+                };
+                let view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                Ok(Some(Frame {
+                    surface_texture: Some(surface_texture),
+                    view,
+                }))
+            }
+            #[cfg(any(test, feature = "testing"))]
+            Output::Offscreen(texture) => Ok(Some(Frame {
+                surface_texture: None,
+                view: texture
+                    .as_ref()
+                    .expect("Offscreen texture exists while output isn't minimized")
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+            })),
+        }
+    }
+
+    /// Returns texture of an acquired frame
+    pub(crate) fn frame_texture<'a>(&'a self, frame: &'a Frame) -> &'a wgpu::Texture {
+        match (&frame.surface_texture, &self.output) {
+            (Some(surface_texture), _) => &surface_texture.texture,
+            #[cfg(any(test, feature = "testing"))]
+            (None, Output::Offscreen(texture)) => texture
+                .as_ref()
+                .expect("Offscreen texture exists while output isn't minimized"),
+            (None, Output::Surface(_)) => unreachable!("Surface frames have surface textures"),
+        }
+    }
+
+    /// Returns whether frames can be copied to CPU memory with [`capture::FrameCapture`]
+    pub(crate) fn can_capture(&self) -> bool {
+        self.output_usage.contains(wgpu::TextureUsages::COPY_SRC)
+            && capture::is_supported_format(self.surface_format)
     }
 
     pub(crate) fn device(&self) -> &wgpu::Device {
@@ -57,29 +201,67 @@ impl Gpu {
         &self.queue
     }
 
-    pub(crate) fn surface(&self) -> &wgpu::Surface {
-        &self.surface
-    }
-
     pub(crate) fn surface_format(&self) -> wgpu::TextureFormat {
         self.surface_format
     }
 
-    async fn get_adapter(instance: &wgpu::Instance, surface: &wgpu::Surface) -> wgpu::Adapter {
+    /// Converts linear color to a value stored in a surface, which is encoded if it is not sRGB
+    ///
+    /// Alpha blending leaves premultiplied colors in a target, so clear color is premultiplied
+    /// as well when a compositor expects it.
+    pub(crate) fn surface_color(&self, color: Color) -> wgpu::Color {
+        let color = match self.alpha_mode {
+            wgpu::CompositeAlphaMode::PreMultiplied => color.premultiply(),
+            _ => color,
+        };
+        if self.surface_format.is_srgb() {
+            return color.into();
+        }
+        let [r, g, b, a] = color.to_srgb();
+        Color::new(r, g, b, a).into()
+    }
+
+    fn configure_surface(&self, surface: &wgpu::Surface) {
+        let config = wgpu::SurfaceConfiguration {
+            usage: self.output_usage,
+            format: self.surface_format,
+            alpha_mode: self.alpha_mode,
+            width: self.size.x,
+            height: self.size.y,
+            present_mode: self.present_mode,
+            view_formats: vec![],
+        };
+        surface.configure(&self.device, &config);
+    }
+
+    fn create_instance(backends: wgpu::Backends) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        })
+    }
+
+    async fn get_adapter(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+        config: &RendererConfig,
+    ) -> Option<wgpu::Adapter> {
         instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(surface),
+                power_preference: config.wgpu_power_preference(),
+                force_fallback_adapter: config.force_fallback_adapter,
+                compatible_surface: surface,
             })
             .await
-            .unwrap()
     }
 
-    async fn get_compatible_device_queue(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    async fn get_compatible_device_queue(
+        adapter: &wgpu::Adapter,
+        config: &RendererConfig,
+    ) -> (wgpu::Device, wgpu::Queue) {
         let device_descriptor = wgpu::DeviceDescriptor {
             label: None,
-            features: wgpu::Features::empty(),
+            features: config.wgpu_features() & adapter.features(),
             limits: wgpu::Limits::default(),
         };
         adapter
@@ -87,4 +269,26 @@ impl Gpu {
             .await
             .unwrap()
     }
+
+    #[cfg(any(test, feature = "testing"))]
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: Vector2<u32>,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen output"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: OFFSCREEN_USAGE,
+            view_formats: &[],
+        })
+    }
 }
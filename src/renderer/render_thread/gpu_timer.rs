@@ -0,0 +1,80 @@
+use std::{sync::mpsc, time::Duration};
+
+use super::gpu::Gpu;
+
+const TIMESTAMP_COUNT: u32 = 2;
+const TIMESTAMPS_SIZE: u64 = TIMESTAMP_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+
+/// Measures time GPU spends on a frame using timestamp queries
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+}
+
+impl GpuTimer {
+    /// Creates timer, if timestamp queries are enabled on a device
+    pub(crate) fn new(gpu: &Gpu) -> Option<GpuTimer> {
+        let device = gpu.device();
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Frame timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let create_buffer = |usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: TIMESTAMPS_SIZE,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        Some(GpuTimer {
+            query_set,
+            resolve_buffer: create_buffer(
+                wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            ),
+            readback_buffer: create_buffer(
+                wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            ),
+        })
+    }
+
+    /// Records a timestamp before frame's commands
+    pub(crate) fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    /// Records a timestamp after frame's commands and copies both to a readable buffer
+    pub(crate) fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            TIMESTAMPS_SIZE,
+        );
+    }
+
+    /// Waits until submitted frame is finished by GPU, returning its duration
+    pub(crate) fn read(&self, gpu: &Gpu) -> Option<Duration> {
+        let slice = self.readback_buffer.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        gpu.device().poll(wgpu::Maintain::Wait);
+        rx.recv().ok()?.ok()?;
+
+        let timestamps: [u64; 2] = bytemuck::pod_read_unaligned(&slice.get_mapped_range());
+        self.readback_buffer.unmap();
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        let nanoseconds = ticks as f64 * gpu.queue().get_timestamp_period() as f64;
+        Some(Duration::from_nanos(nanoseconds as u64))
+    }
+}
@@ -1,28 +1,63 @@
+mod batches;
+#[cfg(test)]
+mod benches;
 mod buffers;
+mod capture;
 mod gpu;
+mod gpu_timer;
 mod pipeline;
+mod post_processing;
+mod stencil;
+mod texture_array;
 mod textures;
 
-use std::{collections::HashMap, sync::mpsc::Receiver};
-
-use crate::renderer::render_thread::buffers::instances::Instance;
+use std::{
+    mem,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
 
 use self::{
+    batches::build_batches,
     buffers::instances::InstanceBuffer,
     buffers::uniform::UniformBuffer,
+    capture::FrameCapture,
     gpu::Gpu,
+    gpu_timer::GpuTimer,
     pipeline::{Pipeline, PipelineBuffers, RenderPass},
+    post_processing::PostProcessing,
+    stencil::StencilTarget,
     textures::Textures,
 };
 use cgmath::Vector2;
+use image::RgbaImage;
 
-use super::{texture_ref::TextureRef, CompatibleWindow, RenderCommands};
+use super::{
+    config::RendererConfig,
+    frame::{CloseOnDrop, FramePacing},
+    layers::sort_by_layer,
+    post_effect::PostEffect,
+    recording::FrameSampler,
+    stats::FrameStats,
+    texture_ref::TextureRef,
+    CompatibleWindow, RenderCommands, RenderError, TextureBatching,
+};
 
 #[derive(Debug)]
 pub(crate) enum RenderThreadMessage {
     Resize(Vector2<u32>),
-    Render(RenderCommands),
+    Render(RenderCommands, u64),
     LoadTexture(TextureRef, Vec<u8>, Vector2<u32>),
+    UnloadTexture(TextureRef),
+    SetPostEffects(Vec<PostEffect>),
+    SetTextureBatching(TextureBatching),
+    CaptureNextFrame(Sender<RgbaImage>),
+    StartRecording(FrameSampler),
+    StopRecording,
+    Shutdown,
 }
 
 pub(crate) struct RendererThread {
@@ -31,15 +66,43 @@ pub(crate) struct RendererThread {
     uniform: UniformBuffer,
     textures: Textures,
     instances: InstanceBuffer,
+    post_processing: PostProcessing,
+    /// Created once output has non-zero size, like post processing targets
+    stencil: Option<StencilTarget>,
+    timer: Option<GpuTimer>,
+    capture_requests: Vec<Sender<RgbaImage>>,
+    recording: Option<FrameSampler>,
+    size: Vector2<u32>,
 }
 
 impl RendererThread {
     pub(crate) fn compatible_with(
         window: impl CompatibleWindow,
         size: impl Into<Vector2<u32>>,
+        config: &RendererConfig,
     ) -> RendererThread {
         let size = size.into();
-        let gpu = Gpu::compatible_with(window, size);
+        let gpu = Gpu::compatible_with(window, size, config);
+        RendererThread::new(gpu, size)
+    }
+
+    /// Creates render thread drawing to an offscreen texture, if any adapter is available
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn headless(
+        size: impl Into<Vector2<u32>>,
+        config: &RendererConfig,
+    ) -> Option<RendererThread> {
+        let size = size.into();
+        let gpu = Gpu::headless(size, config)?;
+        Some(RendererThread::new(gpu, size))
+    }
+
+    /// Returns whether rendered frames can be copied, e.g. to be recorded
+    pub(crate) fn can_capture(&self) -> bool {
+        self.gpu.can_capture()
+    }
+
+    fn new(gpu: Gpu, size: Vector2<u32>) -> RendererThread {
         let uniform = UniformBuffer::new(&gpu, size);
         let texutres = Textures::new(&gpu);
         let instances = InstanceBuffer::new(&gpu);
@@ -51,6 +114,9 @@ impl RendererThread {
                 instances: &instances,
             },
         );
+        let post_processing = PostProcessing::new(&gpu, size);
+        let stencil = (!gpu.is_minimized()).then(|| StencilTarget::new(&gpu, size));
+        let timer = GpuTimer::new(&gpu);
 
         RendererThread {
             gpu,
@@ -58,72 +124,389 @@ impl RendererThread {
             uniform,
             textures: texutres,
             instances,
+            post_processing,
+            stencil,
+            timer,
+            capture_requests: vec![],
+            recording: None,
+            size,
         }
     }
 
-    pub(crate) fn run(mut self, rx: Receiver<RenderThreadMessage>) {
+    pub(crate) fn run(
+        mut self,
+        rx: Receiver<RenderThreadMessage>,
+        errors: Sender<RenderError>,
+        frame_pacing: Arc<FramePacing>,
+        stats: Arc<Mutex<Option<FrameStats>>>,
+    ) {
+        let _close_frame_pacing = CloseOnDrop(Arc::clone(&frame_pacing));
         for command in rx {
             match command {
                 RenderThreadMessage::Resize(size) => self.resize(size),
-                RenderThreadMessage::Render(command) => self.render(command),
+                RenderThreadMessage::Render(command, index) => {
+                    let frame_stats = self.render(command).unwrap_or_else(|error| {
+                        // Renderer might be dropped already, and there is no one to report to
+                        let _ = errors.send(error);
+                        None
+                    });
+                    let presented = frame_stats.is_some();
+                    if let Some(frame_stats) = frame_stats {
+                        *stats.lock().unwrap() = Some(FrameStats {
+                            frame: index,
+                            ..frame_stats
+                        });
+                    }
+                    frame_pacing.finish_frame(index, presented);
+                }
                 RenderThreadMessage::LoadTexture(id, data, size) => {
                     self.textures.load_texture(&self.gpu, id, &data, size)
                 }
+                RenderThreadMessage::UnloadTexture(id) => self.textures.unload_texture(&id),
+                RenderThreadMessage::SetPostEffects(effects) => {
+                    self.post_processing.set_effects(&self.gpu, effects)
+                }
+                RenderThreadMessage::SetTextureBatching(batching) => self
+                    .textures
+                    .set_array_enabled(&self.gpu, batching == TextureBatching::TextureArray),
+                RenderThreadMessage::CaptureNextFrame(tx) => {
+                    // Dropping the sender tells that the frame will never be captured
+                    if self.gpu.can_capture() {
+                        self.capture_requests.push(tx);
+                    }
+                }
+                RenderThreadMessage::StartRecording(sampler) => {
+                    // Dropping the sampler ends the recording without any frames
+                    if self.gpu.can_capture() {
+                        self.recording = Some(sampler);
+                    }
+                }
+                RenderThreadMessage::StopRecording => self.recording = None,
+                RenderThreadMessage::Shutdown => break,
             }
         }
+        // Lets already submitted work finish before GPU resources are dropped
+        self.gpu.device().poll(wgpu::Maintain::Wait);
     }
 
     fn resize(&mut self, size: Vector2<u32>) {
+        self.size = size;
         self.gpu.resize(size);
+        if self.gpu.is_minimized() {
+            return;
+        }
         self.uniform.change_size(&self.gpu, size);
+        self.post_processing.resize(&self.gpu, size);
+        self.stencil = Some(StencilTarget::new(&self.gpu, size));
     }
 
-    fn render(&mut self, command: RenderCommands) {
-        let frame = self.gpu.surface().get_current_texture().unwrap();
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Renders a frame, returning its statistics if it was presented
+    fn render(&mut self, command: RenderCommands) -> Result<Option<FrameStats>, RenderError> {
+        let Some(frame) = self.gpu.acquire_frame()? else {
+            return Ok(None);
+        };
+        let start = Instant::now();
+        let view = if self.post_processing.is_active() {
+            self.post_processing.scene_view()
+        } else {
+            frame.view()
+        };
 
-        let mut render_passes = HashMap::new();
-        for blit in command.blits {
-            render_passes
-                .entry(blit.texture_id)
-                .or_insert_with(Vec::new)
-                .push(Instance::from_blit(&blit));
+        // Blits of unloaded sprites are skipped, as they cannot be drawn anymore
+        let mut blits = command.blits;
+        blits.retain(|blit| self.textures.is_loaded(&blit.texture_id));
+        let blits = sort_by_layer(blits);
+        let (data, batches) = build_batches(&blits, self.textures.array());
+        self.instances.write_instances(&self.gpu, &data);
+
+        let mut encoder = self
+            .gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if let Some(timer) = &self.timer {
+            timer.begin(&mut encoder);
+        }
+        let pass_stats = self.pipeline.encode_pass(
+            &mut encoder,
+            RenderPass {
+                buffers: PipelineBuffers {
+                    uniform: &self.uniform,
+                    textures: &self.textures,
+                    instances: &self.instances,
+                },
+                view,
+                stencil_view: self
+                    .stencil
+                    .as_ref()
+                    .expect("Stencil exists while output isn't minimized")
+                    .view(),
+                clear_color: command
+                    .clear_color
+                    .map(|color| self.gpu.surface_color(color)),
+                clear_stencil: true,
+                target_size: self.size,
+                batches: &batches,
+            },
+        );
+        if self.post_processing.is_active() {
+            self.post_processing.encode(&mut encoder, frame.view());
+        }
+        let record_frame = self
+            .recording
+            .as_ref()
+            .is_some_and(|recording| recording.wants_frame(start));
+        let capture = (record_frame || !self.capture_requests.is_empty()).then(|| {
+            let texture = self.gpu.frame_texture(&frame);
+            FrameCapture::encode(&self.gpu, &mut encoder, texture, self.size)
+        });
+        if let Some(timer) = &self.timer {
+            timer.end(&mut encoder);
         }
 
-        let mut data = vec![];
-        let mut render_ranges = HashMap::new();
-        for (texture_id, instances) in render_passes.iter_mut() {
-            let from = data.len() as u32;
-            let to = from + instances.len() as u32;
-            render_ranges.insert(texture_id, from..to);
-            data.append(instances)
+        self.gpu.queue().submit(Some(encoder.finish()));
+        frame.present();
+        let cpu_time = start.elapsed();
+
+        if let Some(image) = capture.and_then(|capture| capture.read(&self.gpu)) {
+            for tx in self.capture_requests.drain(..) {
+                let _ = tx.send(image.clone());
+            }
+            if let Some(recording) = self.recording.as_mut().filter(|_| record_frame) {
+                recording.record(image, start);
+            }
         }
 
-        self.instances.write_instances(&self.gpu, &data);
+        Ok(Some(FrameStats {
+            blits: blits.len(),
+            batches: batches.len(),
+            render_passes: 1 + self.post_processing.pass_count(),
+            instance_bytes: mem::size_of_val(data.as_slice()) as u64,
+            cpu_time,
+            gpu_time: self.timer.as_ref().and_then(|timer| timer.read(&self.gpu)),
+            ..pass_stats
+        }))
+    }
+}
 
-        let command_buffers = render_ranges
-            .iter()
-            .enumerate()
-            .map(|(i, (texture, range))| {
-                let clear_color = if i == 0 { command.clear_color } else { None };
-
-                let pass = RenderPass {
-                    buffers: PipelineBuffers {
-                        uniform: &self.uniform,
-                        textures: &self.textures,
-                        instances: &self.instances,
-                    },
-                    view: &view,
-                    clear_color,
-                    texture: **texture,
-                    instances: range.clone(),
-                };
-                self.pipeline.encode_pass(&self.gpu, pass)
-            });
-
-        self.gpu.queue().submit(command_buffers);
-        frame.present();
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, sync::mpsc, time::Duration};
+
+    use image::{codecs::gif::GifDecoder, AnimationDecoder};
+
+    use crate::renderer::{
+        config::RendererConfig, post_effect::PostEffect, recording::Recording, rect::Rect,
+        sprite::Sprite, testing::skip_without_adapter, texture_ref::TextureRefManager, Color,
+        RenderCommands,
+    };
+
+    use super::RendererThread;
+
+    fn headless(size: (u32, u32)) -> Option<RendererThread> {
+        skip_without_adapter(RendererThread::headless(
+            size,
+            &RendererConfig::default().gpu_timing(true),
+        ))
+    }
+
+    #[test]
+    fn test_frames_are_skipped_while_minimized() {
+        let Some(mut thread) = headless((16, 16)) else {
+            return;
+        };
+        thread.resize((0, 0).into());
+        assert!(thread.gpu.acquire_frame().unwrap().is_none());
+        assert_eq!(Ok(None), thread.render(RenderCommands::default()));
+
+        thread.resize((8, 4).into());
+        assert!(thread.gpu.acquire_frame().unwrap().is_some());
+        assert!(thread.render(RenderCommands::default()).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_created_minimized() {
+        let Some(mut thread) = headless((0, 0)) else {
+            return;
+        };
+        thread
+            .post_processing
+            .set_effects(&thread.gpu, vec![PostEffect::scanlines(0.5, 1.0)]);
+        assert_eq!(Ok(None), thread.render(RenderCommands::default()));
+
+        thread.resize((8, 4).into());
+        let stats = thread.render(RenderCommands::default()).unwrap().unwrap();
+        assert_eq!(2, stats.render_passes);
+    }
+
+    #[test]
+    fn test_render_reports_stats() {
+        let Some(mut thread) = headless((16, 16)) else {
+            return;
+        };
+        let textures = TextureRefManager::new();
+        let sprites: Vec<_> = (0..2)
+            .map(|_| {
+                let texture = textures.next();
+                thread.textures.load_texture(
+                    &thread.gpu,
+                    texture,
+                    &[255; 4 * 4 * 4],
+                    (4, 4).into(),
+                );
+                Sprite {
+                    texture,
+                    size: (4, 4).into(),
+                }
+            })
+            .collect();
+        thread
+            .post_processing
+            .set_effects(&thread.gpu, vec![PostEffect::vignette(0.5, 0.5)]);
+
+        let mut commands = RenderCommands::default();
+        commands.draw(&sprites[0]);
+        commands.draw(&sprites[0]).at((4, 0));
+        commands.draw(&sprites[1]).at((8, 0));
+        let stats = thread.render(commands).unwrap().unwrap();
+        assert_eq!(3, stats.blits);
+        assert_eq!(2, stats.batches);
+        assert_eq!(2, stats.draw_calls);
+        assert_eq!(2, stats.texture_binds);
+        assert_eq!(1, stats.pipeline_switches);
+        assert_eq!(2, stats.render_passes);
+        assert!(stats.instance_bytes > 0);
+    }
+
+    #[test]
+    fn test_instance_buffer_grows_for_many_blits() {
+        let Some(mut thread) = headless((2, 1)) else {
+            return;
+        };
+        let texture = TextureRefManager::new().next();
+        thread
+            .textures
+            .load_texture(&thread.gpu, texture, &[255, 0, 0, 255], (1, 1).into());
+        let sprite = Sprite {
+            texture,
+            size: (1, 1).into(),
+        };
+        let (tx, rx) = mpsc::channel();
+        thread.capture_requests.push(tx);
+
+        let mut commands = RenderCommands::default();
+        commands.set_clear_color(Color::BLUE);
+        for _ in 0..5000 {
+            commands.draw(&sprite);
+        }
+        // Last blit only fits in a grown buffer
+        commands.draw(&sprite).at((1, 0));
+        let stats = thread.render(commands).unwrap().unwrap();
+        assert_eq!(5001, stats.blits);
+        let image = rx.try_recv().unwrap();
+        assert_eq!([255, 0, 0, 255], image.get_pixel(1, 0).0);
+    }
+
+    #[test]
+    fn test_clip_outside_of_target_draws_nothing() {
+        let Some(mut thread) = headless((16, 16)) else {
+            return;
+        };
+        let texture = TextureRefManager::new().next();
+        thread
+            .textures
+            .load_texture(&thread.gpu, texture, &[255; 4 * 4 * 4], (4, 4).into());
+        let sprite = Sprite {
+            texture,
+            size: (4, 4).into(),
+        };
+
+        let mut commands = RenderCommands::default();
+        commands.push_clip(Rect::new((0, 100), (4, 4)));
+        commands.draw(&sprite);
+        commands.pop_clip();
+        let stats = thread.render(commands).unwrap().unwrap();
+        assert_eq!(0, stats.draw_calls);
+    }
+
+    #[test]
+    fn test_texture_array_draws_interleaved_sprites_at_once() {
+        let Some(mut thread) = headless((16, 4)) else {
+            return;
+        };
+        let textures = TextureRefManager::new();
+        let sprites: Vec<_> = [[255, 0, 0, 255], [0, 255, 0, 128]]
+            .into_iter()
+            .map(|pixel| {
+                let texture = textures.next();
+                let data = pixel.repeat(4 * 4);
+                thread
+                    .textures
+                    .load_texture(&thread.gpu, texture, &data, (4, 4).into());
+                Sprite {
+                    texture,
+                    size: (4, 4).into(),
+                }
+            })
+            .collect();
+        let render = |thread: &mut RendererThread| {
+            let (tx, rx) = mpsc::channel();
+            thread.capture_requests.push(tx);
+            let mut commands = RenderCommands::default();
+            for x in 0..4 {
+                commands.draw(&sprites[x % 2]).at((x as u32 * 3, 0));
+            }
+            let stats = thread.render(commands).unwrap().unwrap();
+            (stats.draw_calls, rx.try_recv().unwrap())
+        };
+
+        let (per_texture_calls, per_texture) = render(&mut thread);
+        thread.textures.set_array_enabled(&thread.gpu, true);
+        let (array_calls, array) = render(&mut thread);
+        assert_eq!(4, per_texture_calls);
+        assert_eq!(1, array_calls);
+        assert_eq!(per_texture, array);
+    }
+
+    #[test]
+    fn test_capture_next_frame() {
+        let Some(mut thread) = headless((3, 2)) else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        thread.capture_requests.push(tx);
+
+        let mut commands = RenderCommands::default();
+        commands.set_clear_color(Color::rgba8(255, 136, 0, 255));
+        thread.render(commands).unwrap();
+        let image = rx.try_recv().unwrap();
+        assert_eq!((3, 2), image.dimensions());
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 136, 0, 255]));
+        assert!(thread.capture_requests.is_empty());
+    }
+
+    #[test]
+    fn test_recording_to_gif() {
+        let Some(mut thread) = headless((4, 4)) else {
+            return;
+        };
+        let path =
+            std::env::temp_dir().join(format!("floppa2_test_recording_{}.gif", std::process::id()));
+        let (recording, sampler) = Recording::start(&path, 1000).unwrap();
+        thread.recording = Some(sampler);
+        for color in [Color::RED, Color::GREEN] {
+            let mut commands = RenderCommands::default();
+            commands.set_clear_color(color);
+            thread.render(commands).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        thread.recording = None;
+        recording.finish().unwrap();
+
+        let decoder = GifDecoder::new(File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(2, frames.len());
+        assert_eq!([255, 0, 0, 255], frames[0].buffer().get_pixel(0, 0).0);
+        assert_eq!([0, 255, 0, 255], frames[1].buffer().get_pixel(3, 3).0);
     }
 }
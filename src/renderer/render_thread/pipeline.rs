@@ -1,14 +1,16 @@
-use wgpu::{include_wgsl, util::DeviceExt, CommandBuffer, VertexAttribute};
+use cgmath::Vector2;
+use wgpu::{util::DeviceExt, VertexAttribute};
 
-use crate::renderer::texture_ref::TextureRef;
+use crate::renderer::{rect::Rect, stats::FrameStats, Stencil};
 
 use super::{
-    buffers::instances::InstanceBuffer, buffers::uniform::UniformBuffer, gpu::Gpu,
-    textures::Textures,
+    batches::Batch, buffers::instances::InstanceBuffer, buffers::uniform::UniformBuffer, gpu::Gpu,
+    stencil::STENCIL_FORMAT, textures::Textures,
 };
 
 pub(crate) struct Pipeline {
-    pipeline: wgpu::RenderPipeline,
+    sprites: StencilPipelines,
+    array_sprites: StencilPipelines,
     blit_buffer: wgpu::Buffer,
 }
 
@@ -21,51 +23,147 @@ pub(crate) struct PipelineBuffers<'a> {
 pub(crate) struct RenderPass<'a> {
     pub(crate) buffers: PipelineBuffers<'a>,
     pub(crate) view: &'a wgpu::TextureView,
+    pub(crate) stencil_view: &'a wgpu::TextureView,
     pub(crate) clear_color: Option<wgpu::Color>,
-    pub(crate) texture: TextureRef,
-    pub(crate) instances: std::ops::Range<u32>,
+    pub(crate) clear_stencil: bool,
+    pub(crate) target_size: Vector2<u32>,
+    pub(crate) batches: &'a [Batch],
 }
 
-impl Pipeline {
-    pub fn new(gpu: &Gpu, buffers: PipelineBuffers<'_>) -> Pipeline {
+/// Variants of a pipeline for every way blits can interact with a stencil
+struct StencilPipelines {
+    draw: wgpu::RenderPipeline,
+    mask_write: wgpu::RenderPipeline,
+    mask_erase: wgpu::RenderPipeline,
+}
+
+impl StencilPipelines {
+    fn new(
+        gpu: &Gpu,
+        shader_source: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+    ) -> StencilPipelines {
         let device = gpu.device();
 
-        let shader_desc = include_wgsl!("shader.wgsl");
-        let shader = device.create_shader_module(shader_desc);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "const ENCODE_SRGB: bool = {};\n{}",
+                    !gpu.surface_format().is_srgb(),
+                    shader_source
+                )
+                .into(),
+            ),
+        });
 
         let pipeline_layout_desc = wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[
-                buffers.uniform.bind_group_layout(),
-                buffers.textures.bind_group_layout(),
-            ],
+            bind_group_layouts,
             push_constant_ranges: &[],
         };
         let pipeline_layout = device.create_pipeline_layout(&pipeline_layout_desc);
 
-        let targets = vec![Some(gpu.surface_format().into())];
-        let pipeline_desc = wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::layout(), InstanceBuffer::layout()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &targets,
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+        let create_pipeline = |fragment_entry_point, write_mask, pass_op| {
+            let targets = vec![Some(wgpu::ColorTargetState {
+                write_mask,
+                // Translucent sprites blend with the target. Only sRGB targets blend in linear
+                // space like the software renderer, encoded surfaces blend encoded values
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                ..gpu.surface_format().into()
+            })];
+            let stencil_face = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Equal,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op,
+            };
+            let pipeline_desc = wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::layout(), InstanceBuffer::layout()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment_entry_point,
+                    targets: &targets,
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: STENCIL_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState {
+                        front: stencil_face,
+                        back: stencil_face,
+                        read_mask: 0xff,
+                        write_mask: 0xff,
+                    },
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            };
+            device.create_render_pipeline(&pipeline_desc)
         };
-        let pipeline = device.create_render_pipeline(&pipeline_desc);
+        StencilPipelines {
+            draw: create_pipeline(
+                "fs_main",
+                wgpu::ColorWrites::ALL,
+                wgpu::StencilOperation::Keep,
+            ),
+            mask_write: create_pipeline(
+                "fs_mask",
+                wgpu::ColorWrites::empty(),
+                wgpu::StencilOperation::IncrementClamp,
+            ),
+            mask_erase: create_pipeline(
+                "fs_mask",
+                wgpu::ColorWrites::empty(),
+                wgpu::StencilOperation::DecrementClamp,
+            ),
+        }
+    }
+
+    /// Returns pipeline and stencil reference for a given stencil operation
+    fn for_stencil(&self, stencil: Stencil) -> (&wgpu::RenderPipeline, u8) {
+        match stencil {
+            Stencil::Test(depth) => (&self.draw, depth),
+            Stencil::Write(depth) => (&self.mask_write, depth),
+            Stencil::Erase(depth) => (&self.mask_erase, depth),
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn new(gpu: &Gpu, buffers: PipelineBuffers<'_>) -> Pipeline {
+        let device = gpu.device();
+
+        let sprites = StencilPipelines::new(
+            gpu,
+            concat!(include_str!("shader.wgsl"), include_str!("sprite.wgsl")),
+            &[
+                buffers.uniform.bind_group_layout(),
+                buffers.textures.bind_group_layout(),
+            ],
+        );
+        let array_sprites = StencilPipelines::new(
+            gpu,
+            concat!(
+                include_str!("shader.wgsl"),
+                include_str!("sprite_array.wgsl")
+            ),
+            &[
+                buffers.uniform.bind_group_layout(),
+                buffers.textures.array_bind_group_layout(),
+            ],
+        );
 
         let blit_buffer_desc = wgpu::util::BufferInitDescriptor {
             label: None,
@@ -75,46 +173,118 @@ impl Pipeline {
         let blit_buffer = device.create_buffer_init(&blit_buffer_desc);
 
         Pipeline {
-            pipeline,
+            sprites,
+            array_sprites,
             blit_buffer,
         }
     }
 
-    pub fn encode_pass(&mut self, gpu: &Gpu, pass: RenderPass<'_>) -> CommandBuffer {
-        let device = gpu.device();
-
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: pass.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: if let Some(c) = pass.clear_color {
-                            wgpu::LoadOp::Clear(c)
-                        } else {
-                            wgpu::LoadOp::Load
-                        },
-                        store: true,
+    /// Records all batches into a single render pass, switching state only between batches
+    ///
+    /// Returns statistics with numbers of draw calls and state changes filled.
+    pub fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pass: RenderPass<'_>,
+    ) -> FrameStats {
+        let mut stats = FrameStats::default();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: pass.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if let Some(c) = pass.clear_color {
+                        wgpu::LoadOp::Clear(c)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: pass.stencil_view,
+                depth_ops: None,
+                stencil_ops: Some(wgpu::Operations {
+                    load: if pass.clear_stencil {
+                        wgpu::LoadOp::Clear(0)
+                    } else {
+                        wgpu::LoadOp::Load
                     },
-                })],
-                depth_stencil_attachment: None,
-            });
-            rpass.set_pipeline(&self.pipeline);
-            rpass.set_vertex_buffer(0, self.blit_buffer.slice(..));
-            rpass.set_vertex_buffer(1, pass.buffers.instances.buffer().slice(..));
-            rpass.set_bind_group(0, pass.buffers.uniform.bind_group(), &[]);
-            rpass.set_bind_group(
-                1,
-                pass.buffers.textures.bind_group_for_texture(&pass.texture),
-                &[],
+                    store: true,
+                }),
+            }),
+        });
+        rpass.set_vertex_buffer(0, self.blit_buffer.slice(..));
+        rpass.set_vertex_buffer(1, pass.buffers.instances.buffer().slice(..));
+        rpass.set_bind_group(0, pass.buffers.uniform.bind_group(), &[]);
+
+        let full_target = Rect::new((0, 0), pass.target_size);
+        let mut current_pipeline = None;
+        let mut current_texture = None;
+        for batch in pass.batches {
+            let scissor = scissor_rect(batch.clip.unwrap_or(full_target), pass.target_size);
+            if scissor.is_empty() {
+                continue;
+            }
+            rpass.set_scissor_rect(
+                scissor.position.x,
+                scissor.position.y,
+                scissor.size.x,
+                scissor.size.y,
             );
-            rpass.draw(0..4, pass.instances as _);
+
+            let pipeline_state = (batch.stencil, batch.texture.is_none());
+            if current_pipeline != Some(pipeline_state) {
+                let pipelines = match batch.texture {
+                    Some(_) => &self.sprites,
+                    None => &self.array_sprites,
+                };
+                let (pipeline, stencil_reference) = pipelines.for_stencil(batch.stencil);
+                rpass.set_pipeline(pipeline);
+                rpass.set_stencil_reference(stencil_reference as u32);
+                current_pipeline = Some(pipeline_state);
+                stats.pipeline_switches += 1;
+            }
+
+            if current_texture != Some(batch.texture) {
+                let bind_group = match &batch.texture {
+                    Some(texture) => pass.buffers.textures.bind_group_for_texture(texture),
+                    None => pass
+                        .buffers
+                        .textures
+                        .array()
+                        .expect("Array batches are built only with texture array")
+                        .bind_group(),
+                };
+                rpass.set_bind_group(1, bind_group, &[]);
+                current_texture = Some(batch.texture);
+                stats.texture_binds += 1;
+            }
+
+            rpass.draw(0..4, batch.instances.clone());
+            stats.draw_calls += 1;
         }
-        encoder.finish()
+        stats
+    }
+}
+
+/// Converts clip rectangle to a scissor rectangle of a render target with a given size
+///
+/// Clip rectangles have origin in bottom-left corner, while scissor rectangles - in top-left one.
+fn scissor_rect(clip: Rect, target_size: Vector2<u32>) -> Rect {
+    let clip = clip.intersection(&Rect::new((0, 0), target_size));
+    // Clips outside of target keep their position, which can be above the target
+    if clip.is_empty() {
+        return Rect::new((0, 0), (0, 0));
     }
+    Rect::new(
+        (
+            clip.position.x,
+            target_size.y - clip.position.y - clip.size.y,
+        ),
+        clip.size,
+    )
 }
 
 #[repr(C)]
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use cgmath::Vector2;
+use wgpu::util::DeviceExt;
+
+use crate::renderer::post_effect::{PostEffect, MAX_POST_EFFECT_PARAMS, PRELUDE};
+
+use super::gpu::Gpu;
+
+struct Target {
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+struct EffectPass {
+    shader: String,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+pub(crate) struct PostProcessing {
+    effects: Vec<EffectPass>,
+    pipelines: HashMap<String, wgpu::RenderPipeline>,
+    targets: Vec<Target>,
+    size: Vector2<u32>,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl PostProcessing {
+    pub(crate) fn new(gpu: &Gpu, size: Vector2<u32>) -> PostProcessing {
+        let device = gpu.device();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        PostProcessing {
+            effects: vec![],
+            pipelines: HashMap::new(),
+            targets: vec![],
+            size,
+            sampler,
+            bind_group_layout,
+            pipeline_layout,
+        }
+    }
+
+    /// Returns whether frame should be rendered to [`PostProcessing::scene_view`] instead of the surface
+    pub(crate) fn is_active(&self) -> bool {
+        !self.effects.is_empty()
+    }
+
+    /// Returns number of render passes recorded by [`PostProcessing::encode`]
+    pub(crate) fn pass_count(&self) -> usize {
+        self.effects.len()
+    }
+
+    /// View scene should be rendered into when post processing is active
+    pub(crate) fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    pub(crate) fn set_effects(&mut self, gpu: &Gpu, effects: Vec<PostEffect>) {
+        for effect in effects.iter() {
+            if !self.pipelines.contains_key(effect.shader.as_ref()) {
+                let pipeline = self.create_pipeline(gpu, &effect.shader);
+                self.pipelines.insert(effect.shader.to_string(), pipeline);
+            }
+        }
+
+        self.effects = effects
+            .into_iter()
+            .map(|effect| {
+                let uniform = PostUniform::new(self.size, &effect.params);
+                let uniform_buffer =
+                    gpu.device()
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: None,
+                            contents: bytemuck::cast_slice(&[uniform]),
+                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        });
+                EffectPass {
+                    shader: effect.shader.into_owned(),
+                    uniform_buffer,
+                    bind_group: None,
+                }
+            })
+            .collect();
+        self.pipelines
+            .retain(|shader, _| self.effects.iter().any(|effect| &effect.shader == shader));
+
+        self.recreate_targets(gpu);
+    }
+
+    pub(crate) fn resize(&mut self, gpu: &Gpu, size: Vector2<u32>) {
+        self.size = size;
+        for effect in self.effects.iter() {
+            gpu.queue().write_buffer(
+                &effect.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&PostUniform::resolution(size)),
+            );
+        }
+        self.recreate_targets(gpu);
+    }
+
+    /// Runs effect chain on a scene rendered to [`PostProcessing::scene_view`], writing result to `output`
+    pub(crate) fn encode(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        for (i, effect) in self.effects.iter().enumerate() {
+            let target = if i + 1 == self.effects.len() {
+                output
+            } else {
+                &self.targets[(i + 1) % 2].view
+            };
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipelines[&effect.shader]);
+            rpass.set_bind_group(
+                0,
+                effect
+                    .bind_group
+                    .as_ref()
+                    .expect("Should be created with targets"),
+                &[],
+            );
+            rpass.draw(0..3, 0..1);
+        }
+    }
+
+    fn create_pipeline(&self, gpu: &Gpu, shader: &str) -> wgpu::RenderPipeline {
+        let device = gpu.device();
+        let source = format!("{}\n{}", PRELUDE, shader);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post effect"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let targets = vec![Some(gpu.surface_format().into())];
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_post",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &targets,
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn recreate_targets(&mut self, gpu: &Gpu) {
+        // Targets of a minimized output are created once it's resized
+        if self.effects.is_empty() || self.size.x == 0 || self.size.y == 0 {
+            self.targets.clear();
+            return;
+        }
+
+        let target_count = self.effects.len().min(2);
+        self.targets = (0..target_count)
+            .map(|i| Self::create_target(gpu, self.size, i))
+            .collect();
+        for (i, effect) in self.effects.iter_mut().enumerate() {
+            let bind_group = gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.targets[i % 2].view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: effect.uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+            effect.bind_group = Some(bind_group);
+        }
+    }
+
+    fn create_target(gpu: &Gpu, size: Vector2<u32>, index: usize) -> Target {
+        let label = format!("Post processing target {}", index);
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(&label),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: gpu.surface_format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Target {
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    resolution: [f32; 2],
+    _padding: [f32; 2],
+    params: [f32; MAX_POST_EFFECT_PARAMS],
+}
+
+impl PostUniform {
+    fn new(size: Vector2<u32>, params: &[f32; MAX_POST_EFFECT_PARAMS]) -> PostUniform {
+        PostUniform {
+            resolution: Self::resolution(size),
+            _padding: [0.0; 2],
+            params: *params,
+        }
+    }
+
+    fn resolution(size: Vector2<u32>) -> [f32; 2] {
+        [size.x as f32, size.y as f32]
+    }
+}
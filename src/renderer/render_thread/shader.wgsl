@@ -15,6 +15,7 @@ struct InstanceInput {
     @location(12) model_matrix_2: vec4<f32>,
     @location(13) model_matrix_3: vec4<f32>,
     @location(14) color: vec4<f32>,
+    @location(15) texture_region: vec4<f32>,
 };
 
 
@@ -22,6 +23,7 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) uv_position: vec2<f32>,
+    @location(2) @interpolate(flat) texture_region: vec4<f32>,
 };
 
 @vertex
@@ -39,16 +41,34 @@ fn vs_main(
     out.clip_position = uniform_.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
     out.color = instance.color;
     out.uv_position = model.uv_position;
+    out.texture_region = instance.texture_region;
     return out;
 }
 
 
-@group(1) @binding(0)
-var texture: texture_2d<f32>;
-@group(1)@binding(1)
-var sampler_: sampler;
+// Surfaces without sRGB formats store encoded values, so they have to be encoded manually
+fn encode_output(color: vec4<f32>) -> vec4<f32> {
+    if !ENCODE_SRGB {
+        return color;
+    }
+    let linear = max(color.rgb, vec3<f32>(0.0));
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return vec4<f32>(select(high, low, linear <= vec3<f32>(0.0031308)), color.a);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(texture, sampler_, in.uv_position) * in.color;
-}
\ No newline at end of file
+    return encode_output(sample_sprite(in) * in.color);
+}
+
+const MASK_ALPHA_THRESHOLD: f32 = 0.5;
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_sprite(in) * in.color;
+    if color.a < MASK_ALPHA_THRESHOLD {
+        discard;
+    }
+    return color;
+}
@@ -0,0 +1,8 @@
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+@group(1)@binding(1)
+var sampler_: sampler;
+
+fn sample_sprite(in: VertexOutput) -> vec4<f32> {
+    return textureSample(texture, sampler_, in.uv_position);
+}
@@ -0,0 +1,16 @@
+// texture_region.xy - part of a layer covered by a sprite, texture_region.z - layer index
+@group(1) @binding(0)
+var texture: texture_2d_array<f32>;
+@group(1)@binding(1)
+var sampler_: sampler;
+
+fn sample_sprite(in: VertexOutput) -> vec4<f32> {
+    // Keep samples inside of the sprite, so neighbouring empty texels do not bleed in
+    let half_texel = vec2<f32>(0.5) / vec2<f32>(textureDimensions(texture));
+    let uv = clamp(
+        in.uv_position * in.texture_region.xy,
+        half_texel,
+        in.texture_region.xy - half_texel,
+    );
+    return textureSample(texture, sampler_, uv, i32(in.texture_region.z));
+}
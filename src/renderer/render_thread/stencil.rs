@@ -0,0 +1,39 @@
+use cgmath::Vector2;
+
+use super::gpu::Gpu;
+
+pub(crate) const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// Depth/stencil attachment used for sprite masking, sized like the render target
+pub(crate) struct StencilTarget {
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl StencilTarget {
+    pub(crate) fn new(gpu: &Gpu, size: Vector2<u32>) -> StencilTarget {
+        let texture = gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Stencil"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: STENCIL_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        StencilTarget {
+            _texture: texture,
+            view,
+        }
+    }
+
+    pub(crate) fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
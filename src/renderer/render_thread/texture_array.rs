@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use cgmath::Vector2;
+
+use crate::renderer::{texture_ref::TextureRef, TEXTURE_ARRAY_LAYER_SIZE};
+
+use super::gpu::Gpu;
+
+const INITIAL_LAYER_CAPACITY: u32 = 16;
+
+/// Place of a sprite inside a texture array
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ArrayLayer {
+    pub(crate) index: u32,
+    /// Part of a layer covered by a sprite, in texture coordinates
+    pub(crate) uv_scale: [f32; 2],
+}
+
+/// 2D texture array holding every sprite that fits in a single layer, each in its own layer
+pub(crate) struct TextureArray {
+    texture: wgpu::Texture,
+    capacity: u32,
+    layers: HashMap<TextureRef, ArrayLayer>,
+    /// Layers of removed sprites, reused before new ones
+    free_layers: Vec<u32>,
+    next_layer: u32,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextureArray {
+    pub(crate) fn new(
+        gpu: &Gpu,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> TextureArray {
+        let texture = Self::create_texture(gpu, INITIAL_LAYER_CAPACITY);
+        let bind_group = Self::create_bind_group(gpu, &texture, bind_group_layout, sampler);
+        TextureArray {
+            texture,
+            capacity: INITIAL_LAYER_CAPACITY,
+            layers: HashMap::new(),
+            free_layers: Vec::new(),
+            next_layer: 0,
+            bind_group,
+        }
+    }
+
+    /// Copies sprite's texture to a free layer, returning false if it cannot be stored in the array
+    pub(crate) fn insert(
+        &mut self,
+        gpu: &Gpu,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        texture_id: TextureRef,
+        texture: &wgpu::Texture,
+        size: Vector2<u32>,
+    ) -> bool {
+        if size.x > TEXTURE_ARRAY_LAYER_SIZE || size.y > TEXTURE_ARRAY_LAYER_SIZE {
+            // Sprite might have been updated with a bigger image
+            self.remove(&texture_id);
+            return false;
+        }
+        let index = match self.layers.get(&texture_id) {
+            Some(layer) => layer.index,
+            None => match self.free_layers.pop() {
+                Some(index) => index,
+                None => {
+                    let index = self.next_layer;
+                    if index == self.capacity && !self.grow(gpu, bind_group_layout, sampler) {
+                        return false;
+                    }
+                    self.next_layer += 1;
+                    index
+                }
+            },
+        };
+
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_texture(
+            texture.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: index,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu.queue().submit(Some(encoder.finish()));
+
+        let layer_size = TEXTURE_ARRAY_LAYER_SIZE as f32;
+        self.layers.insert(
+            texture_id,
+            ArrayLayer {
+                index,
+                uv_scale: [size.x as f32 / layer_size, size.y as f32 / layer_size],
+            },
+        );
+        true
+    }
+
+    /// Frees sprite's layer, so it can be reused by other sprites
+    pub(crate) fn remove(&mut self, texture_id: &TextureRef) {
+        if let Some(layer) = self.layers.remove(texture_id) {
+            self.free_layers.push(layer.index);
+        }
+    }
+
+    pub(crate) fn layer(&self, texture_id: &TextureRef) -> Option<ArrayLayer> {
+        self.layers.get(texture_id).copied()
+    }
+
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Doubles number of layers, up to a device limit
+    fn grow(
+        &mut self,
+        gpu: &Gpu,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> bool {
+        let max_layers = gpu.device().limits().max_texture_array_layers;
+        let capacity = (self.capacity * 2).min(max_layers);
+        if capacity <= self.capacity {
+            return false;
+        }
+
+        let texture = Self::create_texture(gpu, capacity);
+        let mut encoder = gpu
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: TEXTURE_ARRAY_LAYER_SIZE,
+                height: TEXTURE_ARRAY_LAYER_SIZE,
+                depth_or_array_layers: self.capacity,
+            },
+        );
+        gpu.queue().submit(Some(encoder.finish()));
+
+        self.bind_group = Self::create_bind_group(gpu, &texture, bind_group_layout, sampler);
+        self.texture = texture;
+        self.capacity = capacity;
+        true
+    }
+
+    fn create_texture(gpu: &Gpu, capacity: u32) -> wgpu::Texture {
+        gpu.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture array"),
+            size: wgpu::Extent3d {
+                width: TEXTURE_ARRAY_LAYER_SIZE,
+                height: TEXTURE_ARRAY_LAYER_SIZE,
+                depth_or_array_layers: capacity,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn create_bind_group(
+        gpu: &Gpu,
+        texture: &wgpu::Texture,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        gpu.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
@@ -4,9 +4,11 @@ use cgmath::Vector2;
 
 use crate::renderer::texture_ref::TextureRef;
 
-use super::gpu::Gpu;
+use super::{gpu::Gpu, texture_array::TextureArray};
 
 struct TextureData {
+    texture: wgpu::Texture,
+    size: Vector2<u32>,
     texture_bind_group: wgpu::BindGroup,
 }
 
@@ -14,6 +16,8 @@ pub(crate) struct Textures {
     map: HashMap<TextureRef, TextureData>,
     sampler: wgpu::Sampler,
     bind_group_layout: wgpu::BindGroupLayout,
+    array_bind_group_layout: wgpu::BindGroupLayout,
+    array: Option<TextureArray>,
 }
 
 impl Textures {
@@ -31,32 +35,62 @@ impl Textures {
         };
         let sampler = device.create_sampler(&sampler_descriptor);
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        let create_bind_group_layout = |view_dimension| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
                     },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            })
+        };
+        let bind_group_layout = create_bind_group_layout(wgpu::TextureViewDimension::D2);
+        let array_bind_group_layout = create_bind_group_layout(wgpu::TextureViewDimension::D2Array);
         Textures {
             map: HashMap::new(),
             sampler,
             bind_group_layout,
+            array_bind_group_layout,
+            array: None,
+        }
+    }
+
+    /// Enables or disables copying every sprite which fits into a texture array
+    pub(crate) fn set_array_enabled(&mut self, gpu: &Gpu, enabled: bool) {
+        if !enabled {
+            self.array = None;
+            return;
+        }
+        if self.array.is_some() {
+            return;
+        }
+
+        let mut array = TextureArray::new(gpu, &self.array_bind_group_layout, &self.sampler);
+        for (texture_id, data) in self.map.iter() {
+            array.insert(
+                gpu,
+                &self.array_bind_group_layout,
+                &self.sampler,
+                *texture_id,
+                &data.texture,
+                data.size,
+            );
         }
+        self.array = Some(array);
     }
 
     pub(crate) fn load_texture(
@@ -79,7 +113,9 @@ impl Textures {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         };
         let texture = gpu.device().create_texture(&texture_descriptor);
@@ -120,14 +156,52 @@ impl Textures {
             .device()
             .create_bind_group(&texture_bind_group_descriptor);
 
-        self.map
-            .insert(texture_id, TextureData { texture_bind_group });
+        if let Some(array) = self.array.as_mut() {
+            array.insert(
+                gpu,
+                &self.array_bind_group_layout,
+                &self.sampler,
+                texture_id,
+                &texture,
+                size,
+            );
+        }
+
+        self.map.insert(
+            texture_id,
+            TextureData {
+                texture,
+                size,
+                texture_bind_group,
+            },
+        );
+    }
+
+    /// Drops sprite's texture, frames already submitted keep it alive until they finish
+    pub(crate) fn unload_texture(&mut self, texture_id: &TextureRef) {
+        self.map.remove(texture_id);
+        if let Some(array) = self.array.as_mut() {
+            array.remove(texture_id);
+        }
+    }
+
+    pub(crate) fn is_loaded(&self, texture_id: &TextureRef) -> bool {
+        self.map.contains_key(texture_id)
     }
 
     pub(crate) fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.bind_group_layout
     }
 
+    pub(crate) fn array_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.array_bind_group_layout
+    }
+
+    /// Returns texture array, if array batching is enabled
+    pub(crate) fn array(&self) -> Option<&TextureArray> {
+        self.array.as_ref()
+    }
+
     pub(crate) fn bind_group_for_texture(&self, texture_id: &TextureRef) -> &wgpu::BindGroup {
         &self.map[texture_id].texture_bind_group
     }
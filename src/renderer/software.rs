@@ -0,0 +1,227 @@
+use std::{
+    cell::{Ref, RefCell},
+    collections::HashMap,
+};
+
+use cgmath::Vector2;
+use image::{Rgba, RgbaImage};
+
+use super::{
+    backend::{RenderBackend, SpriteFactory},
+    color::linear_to_srgb,
+    layers::sort_by_layer,
+    rect::Rect,
+    sprite::Sprite,
+    texture_ref::{TextureRef, TextureRefManager},
+    BlitCommand, Color, RenderCommands, Stencil, TextureData,
+};
+
+/// Same threshold as GPU mask shader uses
+const MASK_ALPHA_THRESHOLD: f32 = 0.5;
+
+/// Render backend which draws frames on the CPU into an RGBA image, without any GPU adapter
+///
+/// It follows the same rules as a [`Renderer`](super::Renderer) - colors are blended in linear
+/// space and stored as sRGB, sprites are sorted by layers, clipped and masked - so its frames
+/// can be compared with GPU ones rendered to sRGB targets, like headless ones. Surfaces without
+/// sRGB formats, see [`RendererConfig::prefer_srgb`](super::config::RendererConfig::prefer_srgb),
+/// blend encoded colors instead. Post effects and texture batching are not supported.
+pub struct SoftwareRenderer {
+    texture_ref_manager: TextureRefManager,
+    textures: RefCell<HashMap<TextureRef, RgbaImage>>,
+    target: RefCell<Target>,
+}
+
+/// Frame and its stencil buffer
+struct Target {
+    frame: RgbaImage,
+    stencil: Vec<u8>,
+}
+
+impl SoftwareRenderer {
+    /// Creates renderer drawing frames of a given size
+    pub fn new(size: (u32, u32)) -> SoftwareRenderer {
+        SoftwareRenderer {
+            texture_ref_manager: TextureRefManager::new(),
+            textures: RefCell::default(),
+            target: RefCell::new(Target::new(size.into())),
+        }
+    }
+
+    /// Returns the last rendered frame
+    pub fn frame(&self) -> Ref<'_, RgbaImage> {
+        Ref::map(self.target.borrow(), |target| &target.frame)
+    }
+}
+
+impl SpriteFactory for SoftwareRenderer {
+    fn create_sprite(&self, data: impl TextureData) -> Sprite {
+        let sprite = Sprite {
+            texture: self.texture_ref_manager.next(),
+            size: data.size(),
+        };
+        let image = RgbaImage::from_raw(sprite.size.x, sprite.size.y, data.data())
+            .expect("Sprite data should have 4 bytes per pixel");
+        self.textures.borrow_mut().insert(sprite.texture, image);
+        sprite
+    }
+}
+
+impl RenderBackend for SoftwareRenderer {
+    fn render(&self, callback: impl FnOnce(&mut RenderCommands)) {
+        let mut commands = RenderCommands::default();
+        callback(&mut commands);
+
+        let textures = self.textures.borrow();
+        let mut target = self.target.borrow_mut();
+        if let Some(color) = commands.clear_color {
+            target.clear(color);
+        }
+        target.stencil.fill(0);
+        for blit in sort_by_layer(commands.blits) {
+            if let Some(texture) = textures.get(&blit.texture_id) {
+                target.blit(&blit, texture);
+            }
+        }
+    }
+
+    fn resize(&self, size: Vector2<u32>) {
+        *self.target.borrow_mut() = Target::new(size);
+    }
+}
+
+impl Target {
+    fn new(size: Vector2<u32>) -> Target {
+        Target {
+            frame: RgbaImage::new(size.x, size.y),
+            stencil: vec![0; (size.x * size.y) as usize],
+        }
+    }
+
+    fn clear(&mut self, color: Color) {
+        let pixel = Rgba(color.to_rgba8());
+        self.frame.pixels_mut().for_each(|target| *target = pixel);
+    }
+
+    fn blit(&mut self, blit: &BlitCommand, texture: &RgbaImage) {
+        let (width, height) = self.frame.dimensions();
+        let sprite = Rect::new(blit.position, blit.size);
+        let visible = blit
+            .clip
+            .map_or(sprite, |clip| sprite.intersection(&clip))
+            .intersection(&Rect::new((0, 0), (width, height)));
+
+        for y in visible.position.y..visible.position.y + visible.size.y {
+            for x in visible.position.x..visible.position.x + visible.size.x {
+                // Sprites have origin in bottom-left corner, while textures and images - in top-left
+                let texel = Vector2::new(
+                    (x - sprite.position.x) * texture.width() / sprite.size.x,
+                    (sprite.position.y + sprite.size.y - 1 - y) * texture.height() / sprite.size.y,
+                );
+                let color = tint(
+                    Color::from(*texture.get_pixel(texel.x, texel.y)),
+                    blit.color,
+                );
+                let row = height - 1 - y;
+                let stencil = &mut self.stencil[(row * width + x) as usize];
+                match blit.stencil {
+                    Stencil::Test(depth) if *stencil == depth => {
+                        let pixel = self.frame.get_pixel_mut(x, row);
+                        *pixel = blend(color, *pixel);
+                    }
+                    Stencil::Write(depth)
+                        if *stencil == depth && color.a >= MASK_ALPHA_THRESHOLD =>
+                    {
+                        *stencil = stencil.saturating_add(1);
+                    }
+                    Stencil::Erase(depth)
+                        if *stencil == depth && color.a >= MASK_ALPHA_THRESHOLD =>
+                    {
+                        *stencil = stencil.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Multiplies sampled sprite color by blit's color, as GPU shader does
+fn tint(texel: Color, color: Color) -> Color {
+    Color::new(
+        texel.r * color.r,
+        texel.g * color.g,
+        texel.b * color.b,
+        texel.a * color.a,
+    )
+}
+
+/// Blends color over an sRGB pixel the same way as GPU alpha blending does
+fn blend(source: Color, target: Rgba<u8>) -> Rgba<u8> {
+    let target = Color::from(target);
+    let mix = |source_component: f32, target_component: f32| {
+        source_component * source.a + target_component * (1.0 - source.a)
+    };
+    let alpha = source.a + target.a * (1.0 - source.a);
+    let encode = |component: f32| (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgba([
+        encode(linear_to_srgb(mix(source.r, target.r))),
+        encode(linear_to_srgb(mix(source.g, target.g))),
+        encode(linear_to_srgb(mix(source.b, target.b))),
+        encode(alpha),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use crate::renderer::{
+        backend::{RenderBackend, SpriteFactory},
+        testing::{
+            scenes::{self, golden_path, Pixel, SIZE},
+            GoldenImage,
+        },
+        Color, RenderCommands,
+    };
+
+    use super::SoftwareRenderer;
+
+    fn render_scene(
+        scene: impl FnOnce(&SoftwareRenderer, &mut RenderCommands),
+    ) -> SoftwareRenderer {
+        let renderer = SoftwareRenderer::new(SIZE);
+        renderer.render(|commands| scene(&renderer, commands));
+        renderer
+    }
+
+    #[test]
+    fn test_matches_gpu_golden_images() {
+        // Golden images come from headless renderers, which always use sRGB targets - the only
+        // configuration in which GPU blending matches the software renderer
+        let renderer = render_scene(scenes::blits);
+        GoldenImage::new(golden_path("blits.png"), SIZE)
+            .compare_image(&renderer.frame())
+            .unwrap();
+        let renderer = render_scene(scenes::clips_and_masks);
+        GoldenImage::new(golden_path("clips_and_masks.png"), SIZE)
+            .compare_image(&renderer.frame())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_blending_in_linear_space() {
+        let renderer = SoftwareRenderer::new((1, 1));
+        let sprite = renderer.create_sprite(Pixel([255; 4]));
+        renderer.render(|commands| {
+            commands
+                .draw(&sprite)
+                .with_color(Color::WHITE.with_alpha(0.5));
+        });
+        // Half of linear white encoded as sRGB
+        assert_eq!(
+            Rgba([188, 188, 188, 255]),
+            *renderer.frame().get_pixel(0, 0)
+        );
+    }
+}
@@ -13,4 +13,9 @@ impl Sprite {
     pub fn size(&self) -> Vector2<u32> {
         self.size
     }
+
+    /// Returns sprite's ID, which is its index in the order sprites were created in by a renderer
+    pub fn id(&self) -> usize {
+        self.texture.0
+    }
 }
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+/// Statistics of a single frame handled by a render thread
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    /// Index of the frame, as returned by [`FrameHandle::index`](super::frame::FrameHandle::index)
+    pub frame: u64,
+    /// Number of drawn sprites, including masks
+    pub blits: usize,
+    /// Number of groups of consecutive blits sharing texture, clip and stencil state
+    pub batches: usize,
+    /// Number of issued draw calls, which skips batches clipped away entirely
+    pub draw_calls: usize,
+    /// Number of render passes, including post processing ones
+    pub render_passes: usize,
+    /// Number of times a texture was bound
+    pub texture_binds: usize,
+    /// Number of times a render pipeline was switched
+    pub pipeline_switches: usize,
+    /// Size of instance data uploaded to the GPU
+    pub instance_bytes: u64,
+    /// Time render thread spent recording and submitting the frame
+    pub cpu_time: Duration,
+    /// Time GPU spent executing the frame, if enabled with
+    /// [`RendererConfig::gpu_timing`](super::config::RendererConfig::gpu_timing) and supported
+    pub gpu_time: Option<Duration>,
+}
@@ -0,0 +1,372 @@
+//! Golden image regression tests, which compare rendered frames to reference PNG files
+//!
+//! Reference images are created, or updated after intended changes, by running tests with
+//! [`UPDATE_GOLDEN_VAR`] environment variable set.
+
+#[cfg(test)]
+use std::thread;
+use std::{env, error::Error, fmt, io, path::PathBuf};
+
+use image::{ImageError, Rgba, RgbaImage};
+
+use super::{config::RendererConfig, RenderCommands, Renderer};
+
+/// Environment variable which makes comparisons overwrite golden images instead
+pub const UPDATE_GOLDEN_VAR: &str = "FLOPPA2_UPDATE_GOLDEN";
+
+/// Largest difference of a single channel which is still considered a match by default
+pub const DEFAULT_TOLERANCE: u8 = 2;
+
+/// Reference image which frames rendered by a headless renderer are compared to
+#[derive(Debug, Clone)]
+pub struct GoldenImage {
+    path: PathBuf,
+    size: (u32, u32),
+    tolerance: u8,
+    config: RendererConfig,
+}
+
+impl GoldenImage {
+    /// Creates comparison against a PNG file, rendering frames of a given size
+    ///
+    /// Frames are rendered on a software adapter if one is available, so results don't depend on
+    /// a GPU a test runs on.
+    pub fn new(path: impl Into<PathBuf>, size: (u32, u32)) -> GoldenImage {
+        GoldenImage {
+            path: path.into(),
+            size,
+            tolerance: DEFAULT_TOLERANCE,
+            config: RendererConfig::default().force_fallback_adapter(true),
+        }
+    }
+
+    /// Changes largest per-channel difference of a pixel which is still considered a match
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Changes config used to create a headless renderer
+    pub fn config(mut self, config: RendererConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Renders a single frame described by a callback with a headless renderer
+    ///
+    /// Callback can use the renderer to create sprites or set post effects before drawing.
+    /// Returns `None` if no adapter is available.
+    pub fn render(&self, draw: impl FnOnce(&Renderer, &mut RenderCommands)) -> Option<RgbaImage> {
+        let renderer = Renderer::headless(self.size, self.config)?;
+        let frame = renderer.capture_next_frame();
+        renderer.render(|commands| draw(&renderer, commands));
+        frame.recv().ok()
+    }
+
+    /// Renders a frame and compares it to the golden image
+    ///
+    /// On mismatch, rendered frame and an image highlighting differing pixels are saved next to
+    /// the golden image, with `.actual.png` and `.diff.png` suffixes.
+    pub fn compare(
+        &self,
+        draw: impl FnOnce(&Renderer, &mut RenderCommands),
+    ) -> Result<(), GoldenImageError> {
+        let actual = self.render(draw).ok_or(GoldenImageError::NoAdapter)?;
+        if env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+            actual.save(&self.path)?;
+            return Ok(());
+        }
+        self.compare_image(&actual)
+    }
+
+    /// Compares image rendered in another way, e.g. by a
+    /// [`SoftwareRenderer`](super::software::SoftwareRenderer), to the golden image
+    ///
+    /// Unlike [`GoldenImage::compare`], it never updates the golden image.
+    pub fn compare_image(&self, actual: &RgbaImage) -> Result<(), GoldenImageError> {
+        let expected = match image::open(&self.path) {
+            Ok(expected) => expected.to_rgba8(),
+            Err(ImageError::IoError(error)) if error.kind() == io::ErrorKind::NotFound => {
+                actual.save(self.output_path("actual"))?;
+                return Err(GoldenImageError::Missing(self.path.clone()));
+            }
+            Err(error) => return Err(error.into()),
+        };
+        if expected.dimensions() != actual.dimensions() {
+            actual.save(self.output_path("actual"))?;
+            return Err(GoldenImageError::SizeMismatch {
+                expected: expected.dimensions(),
+                actual: actual.dimensions(),
+            });
+        }
+
+        let diff = ImageDiff::new(&expected, actual, self.tolerance);
+        if diff.differing_pixels == 0 {
+            return Ok(());
+        }
+        let diff_path = self.output_path("diff");
+        actual.save(self.output_path("actual"))?;
+        diff.image.save(&diff_path)?;
+        Err(GoldenImageError::Mismatch {
+            path: self.path.clone(),
+            diff_path,
+            differing_pixels: diff.differing_pixels,
+            max_difference: diff.max_difference,
+        })
+    }
+
+    /// Renders a frame and panics if it doesn't match the golden image
+    ///
+    /// Comparison is skipped, with a message, if no adapter is available.
+    pub fn assert_matches(&self, draw: impl FnOnce(&Renderer, &mut RenderCommands)) {
+        match self.compare(draw) {
+            Ok(()) => {}
+            Err(GoldenImageError::NoAdapter) => eprintln!(
+                "No adapter available, skipping golden image {}",
+                self.path.display()
+            ),
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    fn output_path(&self, suffix: &str) -> PathBuf {
+        self.path.with_extension(format!("{suffix}.png"))
+    }
+}
+
+/// Passes a headless renderer, render thread or GPU through, logging that the calling test is
+/// skipped if no adapter was available to create it
+///
+/// Tests use it as `let Some(renderer) = skip_without_adapter(..) else { return; };`, so runs on
+/// machines without adapters show which tests didn't check anything.
+#[cfg(test)]
+pub(crate) fn skip_without_adapter<T>(headless: Option<T>) -> Option<T> {
+    if headless.is_none() {
+        eprintln!(
+            "No adapter available, skipping {}",
+            thread::current().name().unwrap_or("test")
+        );
+    }
+    headless
+}
+
+/// Reason why a rendered frame doesn't match a golden image
+#[derive(Debug)]
+pub enum GoldenImageError {
+    /// No adapter is available to render a frame with
+    NoAdapter,
+    /// Golden image does not exist yet
+    Missing(PathBuf),
+    /// Golden image could not be read, or results could not be saved
+    Image(ImageError),
+    /// Rendered frame has other size than the golden image
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// Some pixels differ by more than the tolerance
+    Mismatch {
+        path: PathBuf,
+        diff_path: PathBuf,
+        differing_pixels: usize,
+        max_difference: u8,
+    },
+}
+
+impl fmt::Display for GoldenImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenImageError::NoAdapter => write!(f, "no adapter available"),
+            GoldenImageError::Missing(path) => write!(
+                f,
+                "golden image {} does not exist, set {UPDATE_GOLDEN_VAR} to create it",
+                path.display()
+            ),
+            GoldenImageError::Image(error) => error.fmt(f),
+            GoldenImageError::SizeMismatch { expected, actual } => write!(
+                f,
+                "rendered frame is {}x{}, but golden image is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            GoldenImageError::Mismatch {
+                path,
+                diff_path,
+                differing_pixels,
+                max_difference,
+            } => write!(
+                f,
+                "{differing_pixels} pixels differ from golden image {} by up to {max_difference}, \
+                see {}",
+                path.display(),
+                diff_path.display()
+            ),
+        }
+    }
+}
+
+impl Error for GoldenImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GoldenImageError::Image(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ImageError> for GoldenImageError {
+    fn from(error: ImageError) -> Self {
+        GoldenImageError::Image(error)
+    }
+}
+
+/// Per-pixel comparison of two images of the same size
+struct ImageDiff {
+    differing_pixels: usize,
+    max_difference: u8,
+    /// Expected image dimmed, with differing pixels marked red
+    image: RgbaImage,
+}
+
+impl ImageDiff {
+    fn new(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> ImageDiff {
+        let mut differing_pixels = 0;
+        let mut max_difference = 0;
+        let image = RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+            let expected = expected.get_pixel(x, y);
+            let difference = expected
+                .0
+                .iter()
+                .zip(actual.get_pixel(x, y).0)
+                .map(|(expected, actual)| expected.abs_diff(actual))
+                .max()
+                .unwrap_or(0);
+            max_difference = max_difference.max(difference);
+            if difference > tolerance {
+                differing_pixels += 1;
+                Rgba([255, 0, 0, 255])
+            } else {
+                let [r, g, b, _] = expected.0.map(|channel| channel / 4);
+                Rgba([r, g, b, 255])
+            }
+        });
+        ImageDiff {
+            differing_pixels,
+            max_difference,
+            image,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{
+        scenes::{self, golden_path, SIZE},
+        GoldenImage, ImageDiff,
+    };
+
+    #[test]
+    fn test_image_diff_respects_tolerance() {
+        let expected = RgbaImage::from_pixel(2, 1, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([102, 100, 100, 255]));
+        actual.put_pixel(1, 0, Rgba([100, 97, 100, 255]));
+
+        let diff = ImageDiff::new(&expected, &actual, 2);
+        assert_eq!(1, diff.differing_pixels);
+        assert_eq!(3, diff.max_difference);
+        assert_eq!(Rgba([25, 25, 25, 255]), *diff.image.get_pixel(0, 0));
+        assert_eq!(Rgba([255, 0, 0, 255]), *diff.image.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn test_golden_blits() {
+        GoldenImage::new(golden_path("blits.png"), SIZE).assert_matches(scenes::blits);
+    }
+
+    #[test]
+    fn test_golden_clips_and_masks() {
+        GoldenImage::new(golden_path("clips_and_masks.png"), SIZE)
+            .assert_matches(scenes::clips_and_masks);
+    }
+}
+
+/// Scenes of golden images checked into this crate, shared by tests of every render backend
+#[cfg(test)]
+pub(crate) mod scenes {
+    use std::path::{Path, PathBuf};
+
+    use cgmath::Vector2;
+    use image::{Rgba, RgbaImage};
+
+    use crate::renderer::{backend::SpriteFactory, rect::Rect, Color, RenderCommands, TextureData};
+
+    pub(crate) const SIZE: (u32, u32) = (16, 12);
+
+    /// Returns path of a golden image checked into this crate
+    pub(crate) fn golden_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name)
+    }
+
+    /// Single pixel sprite of a given RGBA color
+    pub(crate) struct Pixel(pub(crate) [u8; 4]);
+
+    impl TextureData for Pixel {
+        fn data(&self) -> Vec<u8> {
+            self.0.to_vec()
+        }
+
+        fn size(&self) -> Vector2<u32> {
+            (1, 1).into()
+        }
+    }
+
+    /// Sprite with a quarter of each color, which shows how it is placed and flipped
+    struct Quadrants;
+
+    impl TextureData for Quadrants {
+        fn data(&self) -> Vec<u8> {
+            RgbaImage::from_fn(4, 4, |x, y| match (x < 2, y < 2) {
+                (true, true) => Rgba([255, 0, 0, 255]),
+                (false, true) => Rgba([0, 255, 0, 255]),
+                (true, false) => Rgba([0, 0, 255, 255]),
+                (false, false) => Rgba([255, 255, 255, 128]),
+            })
+            .into_raw()
+        }
+
+        fn size(&self) -> Vector2<u32> {
+            (4, 4).into()
+        }
+    }
+
+    pub(crate) fn blits(factory: &impl SpriteFactory, commands: &mut RenderCommands) {
+        let sprite = factory.create_sprite(Quadrants);
+        commands.set_clear_color(Color::rgba8(32, 32, 64, 255));
+        commands.draw(&sprite);
+        commands.draw(&sprite).at((14, 10));
+        commands.draw(&sprite).at((6, 2)).on_layer(1);
+        commands
+            .draw(&sprite)
+            .at((8, 4))
+            .with_color(Color::YELLOW.with_alpha(0.5));
+    }
+
+    pub(crate) fn clips_and_masks(factory: &impl SpriteFactory, commands: &mut RenderCommands) {
+        let sprite = factory.create_sprite(Quadrants);
+        commands.push_clip(Rect::new((1, 1), (3, 3)));
+        commands.draw(&sprite);
+        commands.pop_clip();
+        commands.with_mask(
+            |mask| {
+                mask.draw(&sprite).at((8, 6));
+            },
+            |content| {
+                content.draw(&sprite).at((7, 5)).with_color(Color::CYAN);
+            },
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
-pub(crate) struct TextureRef(usize);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub(crate) struct TextureRef(pub(crate) usize);
+#[derive(Default)]
 pub(crate) struct TextureRefManager {
     next_id: AtomicUsize,
 }
@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::renderer::handle::RendererHandle;
+
+use super::{AsyncRendererExt, LoadStatus, SpriteHandle};
+
+/// Registry of sprites loaded from files, which loads each file only once
+///
+/// Sprites are shared through reference counting - a sprite is unloaded once it belongs to no
+/// group, no other clone of it exists, and [`Assets::unload_unused`] or [`Assets::unload_group`]
+/// is called.
+///
+/// Groups are preloaded from manifest files, which list one image path per line, optionally
+/// prefixed by a name, like `player = sprites/player.png`. Paths are relative to the manifest,
+/// while empty lines and ones starting with `#` are ignored.
+pub struct Assets {
+    renderer: RendererHandle,
+    sprites: HashMap<PathBuf, Asset>,
+    names: HashMap<String, PathBuf>,
+    groups: HashMap<String, Vec<PathBuf>>,
+}
+
+struct Asset {
+    sprite: Arc<SpriteHandle>,
+    /// Number of groups which keep sprite loaded
+    groups: usize,
+}
+
+impl Assets {
+    pub fn new(renderer: RendererHandle) -> Assets {
+        Assets {
+            renderer,
+            sprites: HashMap::new(),
+            names: HashMap::new(),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Returns sprite loaded from a given file, starting to load it if it isn't cached
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Arc<SpriteHandle> {
+        let path = path.as_ref();
+        let asset = self
+            .sprites
+            .entry(path.to_owned())
+            .or_insert_with(|| Asset {
+                sprite: Arc::new(self.renderer.load_sprite_async(path)),
+                groups: 0,
+            });
+        Arc::clone(&asset.sprite)
+    }
+
+    /// Loads sprite like [`Assets::load`] does, making it available by a given name
+    pub fn load_named(
+        &mut self,
+        name: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Arc<SpriteHandle> {
+        let path = path.as_ref();
+        self.names.insert(name.into(), path.to_owned());
+        self.load(path)
+    }
+
+    /// Returns cached sprite with a given name
+    pub fn get(&self, name: &str) -> Option<Arc<SpriteHandle>> {
+        let path = self.names.get(name)?;
+        self.sprites
+            .get(path)
+            .map(|asset| Arc::clone(&asset.sprite))
+    }
+
+    /// Returns whether a sprite loaded from a given file is cached
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.sprites.contains_key(path.as_ref())
+    }
+
+    /// Starts loading every sprite listed in a manifest file, keeping them until group is unloaded
+    ///
+    /// Preloading a group that's already loaded does nothing.
+    pub fn preload_group(
+        &mut self,
+        group: impl Into<String>,
+        manifest: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let group = group.into();
+        if self.groups.contains_key(&group) {
+            return Ok(());
+        }
+        let manifest = manifest.as_ref();
+        let directory = manifest.parent().unwrap_or(Path::new(""));
+        let contents = fs::read_to_string(manifest)?;
+
+        let mut paths = vec![];
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let path = match line.split_once('=') {
+                Some((name, path)) => {
+                    let path = directory.join(path.trim());
+                    self.load_named(name.trim(), &path);
+                    path
+                }
+                None => {
+                    let path = directory.join(line);
+                    self.load(&path);
+                    path
+                }
+            };
+            if let Some(asset) = self.sprites.get_mut(&path) {
+                asset.groups += 1;
+            }
+            paths.push(path);
+        }
+        self.groups.insert(group, paths);
+        Ok(())
+    }
+
+    /// Returns whether every sprite of a group finished loading, successfully or not
+    ///
+    /// Returns false for groups which weren't preloaded.
+    pub fn is_group_loaded(&self, group: &str) -> bool {
+        self.groups.get(group).is_some_and(|paths| {
+            paths
+                .iter()
+                .all(|path| self.sprites[path].sprite.status() != LoadStatus::Loading)
+        })
+    }
+
+    /// Releases sprites of a group, unloading ones which aren't used anymore
+    pub fn unload_group(&mut self, group: &str) {
+        for path in self.groups.remove(group).into_iter().flatten() {
+            if let Some(asset) = self.sprites.get_mut(&path) {
+                asset.groups -= 1;
+            }
+        }
+        self.unload_unused();
+    }
+
+    /// Unloads sprites which belong to no group and aren't referenced outside of the registry
+    pub fn unload_unused(&mut self) {
+        let unused: Vec<_> = self
+            .sprites
+            .iter()
+            .filter(|(_, asset)| asset.groups == 0 && Arc::strong_count(&asset.sprite) == 1)
+            .map(|(path, _)| path.clone())
+            .collect();
+        // Dropping the last reference to a sprite unloads it
+        for path in unused {
+            self.sprites.remove(&path);
+        }
+        let sprites = &self.sprites;
+        self.names.retain(|_, path| sprites.contains_key(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use image::{Rgba, RgbaImage};
+
+    use crate::renderer::{config::RendererConfig, testing::skip_without_adapter, Renderer};
+
+    use super::Assets;
+
+    #[test]
+    fn test_groups_share_and_unload_sprites() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 2), RendererConfig::default()))
+        else {
+            return;
+        };
+        let directory = std::env::temp_dir().join(format!("floppa2_assets_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["tile.png", "player.png"] {
+            RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 255]))
+                .save(directory.join(name))
+                .unwrap();
+        }
+        let manifest = directory.join("level.txt");
+        fs::write(
+            &manifest,
+            "# Level sprites\ntile.png\n\nplayer = player.png\n",
+        )
+        .unwrap();
+
+        let mut assets = Assets::new(renderer.handle());
+        let tile = assets.load(directory.join("tile.png"));
+        assets.preload_group("level", &manifest).unwrap();
+        assert!(Arc::ptr_eq(&tile, &assets.load(directory.join("tile.png"))));
+        let player = assets.get("player").unwrap();
+        player.wait();
+        tile.wait();
+        assert!(assets.is_group_loaded("level"));
+        assert!(player.is_loaded());
+
+        drop(player);
+        assets.unload_group("level");
+        fs::remove_dir_all(&directory).unwrap();
+        // Still used outside of the registry
+        assert!(assets.contains(directory.join("tile.png")));
+        assert!(!assets.contains(directory.join("player.png")));
+        assert!(assets.get("player").is_none());
+
+        drop(tile);
+        assets.unload_unused();
+        assert!(!assets.contains(directory.join("tile.png")));
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use lazy_static::lazy_static;
+
+use crate::renderer::{handle::RendererHandle, sprite::Sprite, Renderer, TextureData};
+
+#[cfg(feature = "watch")]
+use super::watch;
+use super::Image;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+lazy_static! {
+    /// Workers decoding images, shared by all renderers
+    static ref WORKERS: Sender<Job> = spawn_workers();
+}
+
+/// Status of a sprite loaded by [`AsyncRendererExt::load_sprite_async`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadStatus {
+    /// Image is still being decoded, so sprite shows placeholder
+    Loading,
+    /// Image was uploaded into the sprite
+    Loaded,
+    /// Image couldn't be read or decoded, so sprite keeps showing placeholder
+    ///
+    /// With `watch` feature enabled, a failed reload keeps the last successfully loaded image
+    /// instead.
+    Failed(String),
+}
+
+/// Sprite which image is loaded in background
+///
+/// Until the image is decoded, sprite has a 1x1 transparent placeholder texture, so it can be
+/// drawn right away. Sprite's texture is freed when its handle is dropped.
+pub struct SpriteHandle {
+    shared: Arc<Shared>,
+    renderer: RendererHandle,
+}
+
+pub(super) struct Shared {
+    /// Taken out when sprite is unloaded
+    sprite: Mutex<Option<Sprite>>,
+    status: Mutex<LoadStatus>,
+    finished: Condvar,
+}
+
+impl SpriteHandle {
+    /// Returns sprite to draw, which is a placeholder until image is loaded
+    ///
+    /// Sprite stays locked while the guard is alive, so loading can't finish meanwhile. Calling
+    /// [`SpriteHandle::wait`] while holding it deadlocks.
+    pub fn sprite(&self) -> impl Deref<Target = Sprite> + '_ {
+        SpriteGuard(self.shared.sprite.lock().unwrap())
+    }
+
+    /// Returns whether image was loaded into the sprite
+    ///
+    /// Frames rendered after it returns true draw the image instead of the placeholder.
+    pub fn is_loaded(&self) -> bool {
+        self.status() == LoadStatus::Loaded
+    }
+
+    /// Returns current status of loading, without waiting for it to finish
+    pub fn status(&self) -> LoadStatus {
+        self.shared.status.lock().unwrap().clone()
+    }
+
+    /// Blocks until image is loaded or fails to load, e.g. on a loading screen
+    ///
+    /// Deadlocks if a guard returned by [`SpriteHandle::sprite`] is held by the calling thread.
+    pub fn wait(&self) -> LoadStatus {
+        let status = self.shared.status.lock().unwrap();
+        let status = self
+            .shared
+            .finished
+            .wait_while(status, |status| *status == LoadStatus::Loading)
+            .unwrap();
+        status.clone()
+    }
+}
+
+impl Drop for SpriteHandle {
+    /// Frees sprite's texture, image which is still loading is never uploaded
+    fn drop(&mut self) {
+        if let Some(sprite) = self.shared.sprite.lock().unwrap().take() {
+            self.renderer.unload_sprite(sprite);
+        }
+    }
+}
+
+struct SpriteGuard<'a>(MutexGuard<'a, Option<Sprite>>);
+
+impl Deref for SpriteGuard<'_> {
+    type Target = Sprite;
+
+    fn deref(&self) -> &Sprite {
+        self.0
+            .as_ref()
+            .expect("Sprite is unloaded only when its handle is dropped")
+    }
+}
+
+/// Utility methods for loading sprites without blocking a calling thread
+pub trait AsyncRendererExt {
+    /// Starts loading sprite from image file on a worker thread
+    ///
+    /// With `watch` feature enabled, sprite is reloaded whenever the file changes.
+    fn load_sprite_async(&self, path: impl AsRef<Path>) -> SpriteHandle;
+}
+
+impl AsyncRendererExt for RendererHandle {
+    fn load_sprite_async(&self, path: impl AsRef<Path>) -> SpriteHandle {
+        let shared = Arc::new(Shared {
+            sprite: Mutex::new(Some(self.create_sprite(Placeholder))),
+            status: Mutex::new(LoadStatus::Loading),
+            finished: Condvar::new(),
+        });
+        let job = LoadJob {
+            path: path.as_ref().to_owned(),
+            renderer: self.clone(),
+            shared: Arc::clone(&shared),
+        };
+        WORKERS
+            .send(Box::new(move || job.run()))
+            .expect("Sprite loading workers should never stop");
+        SpriteHandle {
+            shared,
+            renderer: self.clone(),
+        }
+    }
+}
+
+impl AsyncRendererExt for Renderer {
+    fn load_sprite_async(&self, path: impl AsRef<Path>) -> SpriteHandle {
+        self.handle().load_sprite_async(path)
+    }
+}
+
+struct LoadJob {
+    path: PathBuf,
+    renderer: RendererHandle,
+    shared: Arc<Shared>,
+}
+
+impl LoadJob {
+    fn run(self) {
+        // Read before decoding, so changes made meanwhile are reloaded later
+        #[cfg(feature = "watch")]
+        let modified = watch::modified(&self.path);
+        self.shared.load(&self.renderer, &self.path);
+        #[cfg(feature = "watch")]
+        watch::watch(
+            self.path,
+            modified,
+            self.renderer,
+            Arc::downgrade(&self.shared),
+        );
+    }
+}
+
+impl Shared {
+    /// Decodes image into the sprite, unless sprite was unloaded meanwhile
+    ///
+    /// Panics while decoding are caught and reported as failures, so waiting threads are woken.
+    pub(super) fn load(&self, renderer: &RendererHandle, path: &Path) {
+        let status = panic::catch_unwind(AssertUnwindSafe(|| match image::open(path) {
+            Ok(image) => {
+                // Converted before locking, so drawing the sprite isn't blocked meanwhile
+                let image = Image(image);
+                let (size, data) = (image.size(), image.data());
+                if let Some(sprite) = self.sprite.lock().unwrap().as_mut() {
+                    renderer.update_sprite_data(sprite, size, data);
+                }
+                LoadStatus::Loaded
+            }
+            Err(error) => LoadStatus::Failed(format!("{}: {}", path.display(), error)),
+        }))
+        .unwrap_or_else(|_| LoadStatus::Failed(format!("{}: decoding panicked", path.display())));
+        *self.status.lock().unwrap() = status;
+        self.finished.notify_all();
+    }
+}
+
+struct Placeholder;
+
+impl TextureData for Placeholder {
+    fn data(&self) -> Vec<u8> {
+        vec![0; 4]
+    }
+
+    fn size(&self) -> cgmath::Vector2<u32> {
+        (1, 1).into()
+    }
+}
+
+fn spawn_workers() -> Sender<Job> {
+    let (tx, rx) = mpsc::channel();
+    let rx = Arc::new(Mutex::new(rx));
+    let count = thread::available_parallelism().map_or(1, usize::from);
+    for index in 0..count {
+        let rx = Arc::clone(&rx);
+        thread::Builder::new()
+            .name(format!("Sprite loader {}", index))
+            .spawn(move || run_worker(&rx))
+            .expect("Failed to spawn sprite loading worker");
+    }
+    tx
+}
+
+fn run_worker(rx: &Mutex<Receiver<Job>>) {
+    loop {
+        // Lock is released before running a job, so other workers can take next ones
+        let job = rx.lock().unwrap().recv();
+        match job {
+            // A panicking job must not take its worker down, as it would never be replaced
+            Ok(job) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::renderer::{config::RendererConfig, testing::skip_without_adapter, Color, Renderer};
+
+    use super::{AsyncRendererExt, LoadStatus, WORKERS};
+
+    #[test]
+    fn test_load_sprite_async() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 2), RendererConfig::default()))
+        else {
+            return;
+        };
+        let path = std::env::temp_dir().join(format!(
+            "floppa2_load_sprite_async_{}.png",
+            std::process::id()
+        ));
+        RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        let missing = renderer.load_sprite_async("does/not/exist.png");
+        let handle = renderer.load_sprite_async(&path);
+        assert_eq!(LoadStatus::Loaded, handle.wait());
+        assert!(handle.is_loaded());
+        assert!(matches!(missing.wait(), LoadStatus::Failed(_)));
+        std::fs::remove_file(&path).unwrap();
+
+        let frame = renderer.capture_next_frame();
+        renderer.render(|commands| {
+            commands.set_clear_color(Color::BLUE);
+            commands.draw(&handle.sprite());
+            commands.draw(&missing.sprite()).at((1, 1));
+        });
+        let frame = frame.recv().unwrap();
+        assert_eq!(Rgba([255, 0, 0, 255]), *frame.get_pixel(1, 1));
+        // Placeholder is transparent
+        assert_eq!(Rgba([0, 0, 255, 255]), *frame.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn test_workers_survive_panicking_jobs() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 2), RendererConfig::default()))
+        else {
+            return;
+        };
+        // Enough to take down every worker if panics weren't caught
+        let count = std::thread::available_parallelism().map_or(1, usize::from);
+        for _ in 0..count {
+            WORKERS.send(Box::new(|| panic!("Job panicked"))).unwrap();
+        }
+        let missing = renderer.load_sprite_async("does/not/exist.png");
+        assert!(matches!(missing.wait(), LoadStatus::Failed(_)));
+    }
+}
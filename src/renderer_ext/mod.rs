@@ -1,8 +1,19 @@
+mod assets;
+mod loader;
+#[cfg(feature = "watch")]
+mod watch;
+
 use std::path::Path;
 
 use image::{io::Reader as ImageReader, DynamicImage, GenericImageView};
 
-use crate::renderer::{sprite::Sprite, Renderer, TextureData};
+use crate::renderer::{backend::SpriteFactory, sprite::Sprite, TextureData};
+
+pub use self::{
+    assets::Assets,
+    loader::{AsyncRendererExt, LoadStatus, SpriteHandle},
+};
+
 pub struct Image(DynamicImage);
 
 impl Image {
@@ -10,6 +21,16 @@ impl Image {
         let image = ImageReader::open(path).unwrap().decode().unwrap();
         Image(image)
     }
+
+    /// Decodes image from bytes of an encoded file, detecting its format
+    pub fn load_from_memory(bytes: &[u8]) -> Image {
+        Image(image::load_from_memory(bytes).unwrap())
+    }
+
+    /// Wraps already decoded image, e.g. one generated at runtime
+    pub fn from_dynamic(image: DynamicImage) -> Image {
+        Image(image)
+    }
 }
 
 impl TextureData for Image {
@@ -22,15 +43,61 @@ impl TextureData for Image {
     }
 }
 
-/// Additional utility methods for renderer
+/// Additional utility methods for renderer, or anything else that can create sprites
 pub trait RendererExt {
     /// Loads sprite from image file
     fn create_sprite_from_file(&self, path: impl AsRef<Path>) -> Sprite;
+
+    /// Loads sprite from bytes of an image file, e.g. ones embedded by [`include_sprite!`](crate::include_sprite)
+    fn create_sprite_from_bytes(&self, bytes: &[u8]) -> Sprite;
 }
 
-impl RendererExt for Renderer {
+impl<T: SpriteFactory> RendererExt for T {
     fn create_sprite_from_file(&self, path: impl AsRef<Path>) -> Sprite {
         let image = Image::load_from_file(path);
         self.create_sprite(image)
     }
+
+    fn create_sprite_from_bytes(&self, bytes: &[u8]) -> Sprite {
+        let image = Image::load_from_memory(bytes);
+        self.create_sprite(image)
+    }
+}
+
+/// Creates sprite from an image file embedded into a binary at compile time
+///
+/// Path is relative to the current file, like for [`include_bytes!`]. Renderer can be anything
+/// that can create sprites.
+///
+/// ```ignore
+/// let tile = include_sprite!(renderer, "../assets/tile.png");
+/// ```
+#[macro_export]
+macro_rules! include_sprite {
+    ($renderer:expr, $path:literal) => {
+        $crate::renderer_ext::RendererExt::create_sprite_from_bytes(
+            &$renderer,
+            include_bytes!($path),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::backend::{RecordingRenderer, SpriteFactory};
+
+    use super::{Image, RendererExt};
+
+    #[test]
+    fn test_embedded_sprite_matches_file() {
+        let renderer = RecordingRenderer::new();
+        let sprite = include_sprite!(renderer, "../../examples/tile.png");
+        renderer.create_sprite_from_file("examples/tile.png");
+        renderer.create_sprite(Image::from_dynamic(image::DynamicImage::new_rgba8(2, 3)));
+
+        let sprites = renderer.sprites();
+        assert_eq!(sprites[1].size, sprite.size());
+        assert_eq!(sprites[1].data, sprites[0].data);
+        assert_eq!(vec![0; 2 * 3 * 4], sprites[2].data);
+    }
 }
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Weak,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use lazy_static::lazy_static;
+
+use crate::renderer::handle::RendererHandle;
+
+use super::loader::Shared;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+lazy_static! {
+    /// Thread polling modification times of every watched file
+    static ref WATCHER: Sender<WatchedSprite> = spawn_watcher();
+}
+
+/// Sprite which is reloaded into the same texture whenever its file changes
+struct WatchedSprite {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    renderer: RendererHandle,
+    shared: Weak<Shared>,
+}
+
+impl WatchedSprite {
+    /// Reloads sprite if its file was modified, returning false once the sprite is dropped
+    fn poll(&mut self) -> bool {
+        let Some(shared) = self.shared.upgrade() else {
+            return false;
+        };
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            shared.load(&self.renderer, &self.path);
+        }
+        true
+    }
+}
+
+/// Returns file's modification time, or None if it cannot be read, e.g. when file is missing
+pub(super) fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Starts watching file a sprite was loaded from, until the sprite is dropped
+pub(super) fn watch(
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    renderer: RendererHandle,
+    shared: Weak<Shared>,
+) {
+    WATCHER
+        .send(WatchedSprite {
+            path,
+            modified,
+            renderer,
+            shared,
+        })
+        .expect("Sprite watcher should never stop");
+}
+
+fn spawn_watcher() -> Sender<WatchedSprite> {
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("Sprite watcher".to_owned())
+        .spawn(move || run_watcher(rx))
+        .expect("Failed to spawn sprite watcher");
+    tx
+}
+
+fn run_watcher(rx: Receiver<WatchedSprite>) {
+    let mut sprites = vec![];
+    loop {
+        sprites.extend(rx.try_iter());
+        sprites.retain_mut(WatchedSprite::poll);
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        thread,
+        time::{Duration, SystemTime},
+    };
+
+    use cgmath::Vector2;
+    use image::{Rgba, RgbaImage};
+
+    use crate::{
+        renderer::{config::RendererConfig, testing::skip_without_adapter, Renderer},
+        renderer_ext::{AsyncRendererExt, LoadStatus},
+    };
+
+    #[test]
+    fn test_modified_file_is_reloaded() {
+        let Some(renderer) =
+            skip_without_adapter(Renderer::headless((2, 2), RendererConfig::default()))
+        else {
+            return;
+        };
+        let path =
+            std::env::temp_dir().join(format!("floppa2_watched_sprite_{}.png", std::process::id()));
+        RgbaImage::new(1, 1).save(&path).unwrap();
+
+        let handle = renderer.load_sprite_async(&path);
+        assert_eq!(LoadStatus::Loaded, handle.wait());
+        RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+        // Guarantees that modification time differs on file systems with coarse timestamps
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let reloaded = (0..40).any(|_| {
+            thread::sleep(Duration::from_millis(50));
+            handle.sprite().size() == Vector2::new(2, 1)
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert!(reloaded);
+    }
+}